@@ -0,0 +1,55 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A comparison of the recursive and iterative parsers on inputs of different shapes.
+//!
+//! Run this with `cargo bench --features iterative-parser`.
+
+use std::{
+  hint::black_box,
+  time::{Duration, Instant},
+};
+
+use luna::syntax::{parse, parse_iterative, Error, SExpr};
+
+/// The number of times to parse each input.
+const ITERATIONS: u32 = 200;
+
+/// Generate a single list nested to the given depth.
+fn deep(depth: usize) -> String {
+  format!("{}x{}", "(".repeat(depth), ")".repeat(depth))
+}
+
+/// Generate a single list with the given number of elements.
+fn wide(width: usize) -> String {
+  format!("({})", vec!["(a 1 \"b\")"; width].join(" "))
+}
+
+/// Measure the average time taken to parse an input with a parser.
+fn time(parser: fn(&str) -> Result<Vec<SExpr>, Error>, input: &str) -> Duration {
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    black_box(parser(black_box(input)).unwrap());
+  }
+
+  start.elapsed() / ITERATIONS
+}
+
+fn main() {
+  let shapes = [
+    ("deep (100)", deep(100)),
+    ("deep (1000)", deep(1000)),
+    ("wide (1000)", wide(1000)),
+    ("wide (10000)", wide(10_000)),
+  ];
+
+  println!("{:<14} {:>12} {:>12}", "shape", "recursive", "iterative");
+  for (name, input) in &shapes {
+    // Both parsers must agree for the comparison to be meaningful.
+    assert_eq!(parse(input).unwrap(), parse_iterative(input).unwrap());
+
+    let recursive = time(parse, input);
+    let iterative = time(parse_iterative, input);
+    println!("{name:<14} {:>12.2?} {:>12.2?}", recursive, iterative);
+  }
+}
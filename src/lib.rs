@@ -13,4 +13,6 @@
   unused_qualifications
 )]
 
+pub mod prelude;
+pub mod repl;
 pub mod syntax;
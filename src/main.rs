@@ -13,30 +13,100 @@
   unused_qualifications
 )]
 
-use std::fs;
+use std::{
+  env,
+  ffi::OsString,
+  fs::{self, OpenOptions},
+  io::{self, IsTerminal, Read},
+  iter,
+  path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use directories_next::ProjectDirs;
 use rustyline::{error::ReadlineError, DefaultEditor};
 
 #[rustfmt::skip]
-use luna::syntax::parse;
+use luna::{repl::{self, ParseHandler}, syntax::{self, format_source, line_col, parse, parse_all, pretty_ast, stats, ParseOptions, Stats}};
+
+/// The name with which to refer to standard input in diagnostics.
+const STDIN_NAME: &str = "<stdin>";
 
 /// Parsed command line arguments.
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
 struct Args {
-  #[arg(name = "FILE", help = "A path to a Scheme program to execute")]
+  #[command(subcommand)]
+  command: Option<Command>,
+  #[arg(
+    name = "FILE",
+    help = "A path to a Scheme program to execute, or - to read it from standard input"
+  )]
   file_path: Option<String>,
+  #[arg(
+    long,
+    requires = "FILE",
+    help = "Print the syntax tree of the program and exit"
+  )]
+  ast: bool,
+  #[arg(
+    long,
+    requires = "FILE",
+    conflicts_with = "ast",
+    help = "Print the program in the canonical style and exit"
+  )]
+  format: bool,
+  #[arg(
+    long,
+    requires = "format",
+    help = "Exit with an error instead if the program is not in the canonical style"
+  )]
+  check: bool,
+}
+
+/// A subcommand to run instead of executing a program.
+// NOTE: Subcommand is not imported, since the code generated by the derive refers to
+//       clap::Subcommand by its full path and so trips the unused_qualifications lint.
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+  /// Print counts of the tokens, forms, and atoms in Scheme programs
+  Stats {
+    #[arg(
+      name = "FILE",
+      required = true,
+      help = "Paths to the Scheme programs to count"
+    )]
+    file_paths: Vec<String>,
+    #[arg(long, help = "Print the counts as a JSON object")]
+    json: bool,
+  },
 }
 
 fn main() -> Result<()> {
   let args = Args::parse();
 
-  if let Some(path) = args.file_path {
-    let input = fs::read_to_string(path)?;
-    println!("{:?}", parse(&input));
+  if let Some(Command::Stats { file_paths, json }) = args.command {
+    print_stats(&file_paths, json)?;
+  } else if let Some(path) = args.file_path {
+    let (input, path) = if path == "-" {
+      (read_stdin()?, STDIN_NAME.to_string())
+    } else {
+      (read_source(Path::new(&path))?, path)
+    };
+    if args.format {
+      format_file(&input, &path, args.check)?;
+    } else if args.ast {
+      match parse(&input) {
+        Ok(tree) => print!("{}", pretty_ast(&tree)),
+        Err(error) => bail!("{}", render_error(error, &input, &path)),
+      }
+    } else {
+      run_file(&input, &path)?;
+    }
+  } else if !io::stdin().is_terminal() {
+    // NOTE: A program that is piped in should be run rather than fed to the REPL.
+    run_file(&read_stdin()?, STDIN_NAME)?;
   } else {
     repl()?;
   }
@@ -46,54 +116,289 @@ fn main() -> Result<()> {
 
 fn repl() -> Result<()> {
   println!("Welcome to Luna v0.1.0!");
-  println!("Press C-d to exit.");
-
-  // The first and second parameters are respectively a reverse domain name and
-  // organisation name, which are currently not used.
-  let dirs = match ProjectDirs::from("", "", "luna") {
-    Some(dirs) => dirs,
-    // TODO: Handle a None value more gracefully by either throwing an error or disabling
-    //       history.
-    None => panic!("Could not find a valid $HOME path."),
-  };
-  // Ensure that the data directory exists to avoid errors when trying to write the
-  // history file.
-  if !dirs.data_dir().exists() {
-    // TODO: Handle errors more gracefully.
-    fs::create_dir(dirs.data_dir())?;
-  }
-  let history_path = dirs.data_dir().join("history.txt");
+  println!("Press C-d to exit, or C-c to abandon the current line.");
 
+  let history_path = history_path();
   let mut rl = DefaultEditor::new()?;
-  if rl.load_history(&history_path).is_err() {
-    println!("No previous history.");
+  if let Some(path) = &history_path {
+    if rl.load_history(path).is_err() {
+      println!("No previous history.");
+    }
   }
 
-  loop {
-    let line = rl.readline("> ");
-    match line {
+  let lines = iter::from_fn(|| loop {
+    match rl.readline("> ") {
       Ok(line) => {
-        rl.add_history_entry(&line)?;
-
-        // TODO: Properly display and format syntax trees.
-        match parse(&line) {
-          Ok(sexpr) => println!("{sexpr:?}"),
-          Err(error) => {
-            // TODO: Implement a unified error type with improved formatting.
-            println!("Syntax error: {error}");
-            println!("context: {}", &line[error.span.start..error.span.end]);
-          },
+        if let Err(error) = rl.add_history_entry(&line) {
+          println!("Error: {error:?}");
+          return None;
         }
+        return Some(line);
       },
-      Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
-      Err(error) => {
-        println!("Error: {error:?}");
-        break;
+      Err(error) => match control_flow(&error) {
+        ControlFlow::Continue => continue,
+        ControlFlow::Exit => {
+          if !matches!(error, ReadlineError::Eof) {
+            println!("Error: {error:?}");
+          }
+          return None;
+        },
       },
     }
+  });
+  repl::run(&mut ParseHandler::default(), lines, &mut io::stdout())?;
+
+  if let Some(path) = &history_path {
+    if let Err(error) = rl.save_history(path) {
+      eprintln!("Warning: could not save history to {}: {error}", path.display());
+    }
   }
 
-  rl.save_history(&history_path)?;
+  Ok(())
+}
+
+/// Find the path of the history file and ensure that it is writable, or warn and
+/// return `None` to disable history if there is no such path.
+fn history_path() -> Option<PathBuf> {
+  // The first and second parameters are respectively a reverse domain name and
+  // organisation name, which are currently not used.
+  let dirs = ProjectDirs::from("", "", "luna");
+  let Some(path) = resolve_history_path(env::var_os("LUNA_HISTORY"), dirs.as_ref())
+  else {
+    eprintln!("Warning: could not find a valid $HOME path, so history is disabled.");
+    return None;
+  };
+
+  // Ensure that the history file can be written before the session begins, rather
+  // than failing once it ends.
+  let writable = match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => ensure_dir(parent),
+    _ => Ok(()),
+  }
+  .and_then(|()| {
+    OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&path)
+      .with_context(|| format!("could not open {}", path.display()))
+  });
+  match writable {
+    Ok(_) => Some(path),
+    Err(error) => {
+      eprintln!("Warning: {error:#}, so history is disabled.");
+      None
+    },
+  }
+}
+
+/// Resolve the path of the history file.
+///
+/// In order of precedence, this is the non-empty value of the `LUNA_HISTORY`
+/// environment variable, or `history.txt` within the data directory of the project,
+/// which respects `XDG_DATA_HOME` on Linux.
+fn resolve_history_path(
+  luna_history: Option<OsString>,
+  dirs: Option<&ProjectDirs>,
+) -> Option<PathBuf> {
+  match luna_history {
+    Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+    _ => dirs.map(|dirs| dirs.data_dir().join("history.txt")),
+  }
+}
+
+/// Format a program in the canonical style, or check that it is already formatted.
+fn format_file(input: &str, path: &str, check: bool) -> Result<()> {
+  match format_source(input) {
+    Ok(output) if check && output != input => bail!("{path} is not formatted"),
+    Ok(_) if check => Ok(()),
+    Ok(output) => {
+      print!("{output}");
+      Ok(())
+    },
+    Err(error) => bail!("{}", render_error(error, input, path)),
+  }
+}
+
+/// Parse a program, printing every form that parsed successfully followed by every
+/// syntax error that was encountered.
+fn run_file(input: &str, path: &str) -> Result<()> {
+  let (program, errors) = parse_all(input);
+  println!("{program:?}");
+
+  let (source, offset) = ParseOptions::default().display_source(input);
+  for error in &errors {
+    let (line, column) = line_col(source, error.span.start + offset);
+    eprintln!("{path}:{line}:{column}: {error}");
+  }
+  match errors.len() {
+    0 => Ok(()),
+    1 => bail!("could not parse {path} due to the previous error"),
+    n => bail!("could not parse {path} due to {n} previous errors"),
+  }
+}
+
+/// Render a syntax error in the style of a `rustc` diagnostic, locating it within the
+/// input as an editor would display it, including any shebang line.
+fn render_error(error: syntax::Error, input: &str, path: &str) -> String {
+  let (source, offset) = ParseOptions::default().display_source(input);
+  let error = syntax::Error { span: error.span.offset(offset), ..error };
+  error.render_rustc(source, path)
+}
+
+/// Print the combined statistics of the programs at the given paths.
+fn print_stats(paths: &[String], json: bool) -> Result<()> {
+  let mut total = Stats::default();
+  for path in paths {
+    let input = read_source(Path::new(path))?;
+    match stats(&input) {
+      Ok(stats) => total = total + stats,
+      Err(error) => bail!("{}", error.render_rustc(&input, path)),
+    }
+  }
+
+  if json {
+    println!("{}", total.to_json());
+  } else {
+    println!("tokens: {}", total.tokens);
+    println!("forms: {}", total.forms);
+    println!("symbols: {}", total.symbols);
+    println!("strings: {}", total.strings);
+    println!("numbers: {}", total.numbers);
+    println!("depth: {}", total.depth);
+  }
 
   Ok(())
 }
+
+/// How the REPL should proceed after failing to read a line.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum ControlFlow {
+  /// Abandon the current input and show a fresh prompt.
+  Continue,
+  /// Exit the REPL.
+  Exit,
+}
+
+/// Decide how the REPL should proceed after failing to read a line.
+///
+/// An interrupt (C-c) only abandons the current input, whereas the end of input (C-d)
+/// or any other error exits the REPL.
+fn control_flow(error: &ReadlineError) -> ControlFlow {
+  match error {
+    ReadlineError::Interrupted => ControlFlow::Continue,
+    _ => ControlFlow::Exit,
+  }
+}
+
+/// Read the source code of a Scheme program from a file.
+fn read_source(path: &Path) -> Result<String> {
+  if path.is_dir() {
+    bail!("expected a file, got a directory: {}", path.display());
+  }
+
+  let bytes =
+    fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+  match String::from_utf8(bytes) {
+    Ok(input) => Ok(input),
+    Err(error) => bail!(
+      "file is not valid UTF-8 at byte {}: {}",
+      error.utf8_error().valid_up_to(),
+      path.display(),
+    ),
+  }
+}
+
+/// Read the source code of a Scheme program from standard input.
+fn read_stdin() -> Result<String> {
+  let mut bytes = Vec::new();
+  io::stdin().read_to_end(&mut bytes).context("could not read standard input")?;
+  match String::from_utf8(bytes) {
+    Ok(input) => Ok(input),
+    Err(error) => bail!(
+      "standard input is not valid UTF-8 at byte {}",
+      error.utf8_error().valid_up_to(),
+    ),
+  }
+}
+
+/// Ensure that a directory and all of its parents exist.
+fn ensure_dir(path: &Path) -> Result<()> {
+  match fs::create_dir_all(path) {
+    // NOTE: create_dir_all already tolerates the directory existing, but another
+    //       process may create it concurrently, so we also ignore AlreadyExists.
+    Err(error) if error.kind() != io::ErrorKind::AlreadyExists => Err(error)
+      .with_context(|| format!("could not create directory {}", path.display())),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn interrupt_continues() {
+    assert_eq!(control_flow(&ReadlineError::Interrupted), ControlFlow::Continue);
+    assert_eq!(control_flow(&ReadlineError::Eof), ControlFlow::Exit);
+
+    let error = io::Error::other("broken terminal");
+    assert_eq!(control_flow(&ReadlineError::Io(error)), ControlFlow::Exit);
+  }
+
+  #[test]
+  fn history_path_precedence() {
+    let dirs = ProjectDirs::from("", "", "luna");
+    let default = dirs.as_ref().map(|dirs| dirs.data_dir().join("history.txt"));
+
+    // LUNA_HISTORY should take precedence over the data directory.
+    let path = resolve_history_path(Some("/tmp/history".into()), dirs.as_ref());
+    assert_eq!(path, Some(PathBuf::from("/tmp/history")));
+    let path = resolve_history_path(Some("/tmp/history".into()), None);
+    assert_eq!(path, Some(PathBuf::from("/tmp/history")));
+
+    // An unset or empty LUNA_HISTORY should fall back to the data directory.
+    assert_eq!(resolve_history_path(None, dirs.as_ref()), default);
+    assert_eq!(resolve_history_path(Some("".into()), dirs.as_ref()), default);
+    assert_eq!(resolve_history_path(None, None), None);
+  }
+
+  #[test]
+  fn read_invalid_source() -> Result<()> {
+    let root = env::temp_dir().join(format!("luna-read-source-{}", std::process::id()));
+    fs::create_dir_all(&root)?;
+
+    let error = read_source(&root).unwrap_err();
+    assert!(error.to_string().starts_with("expected a file, got a directory"));
+
+    let path = root.join("invalid.scm");
+    fs::write(&path, b"(a \xff b)")?;
+    let error = read_source(&path).unwrap_err();
+    assert!(error.to_string().starts_with("file is not valid UTF-8 at byte 3"));
+
+    let path = root.join("valid.scm");
+    fs::write(&path, "(a b)")?;
+    assert_eq!(read_source(&path)?, "(a b)");
+
+    fs::remove_dir_all(root)?;
+    Ok(())
+  }
+
+  #[test]
+  fn ensure_nested_dir() -> Result<()> {
+    let root = env::temp_dir().join(format!("luna-ensure-dir-{}", std::process::id()));
+    let path = root.join("a").join("b");
+
+    ensure_dir(&path)?;
+    assert!(path.is_dir());
+    // Ensuring an existing directory should succeed.
+    ensure_dir(&path)?;
+
+    // Ensuring a path that is blocked by a file should fail.
+    let file = root.join("file");
+    fs::write(&file, "")?;
+    assert!(ensure_dir(&file.join("c")).is_err());
+
+    fs::remove_dir_all(root)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The most commonly used items of Luna, for glob importing.
+//!
+//! ```
+//! use luna::prelude::*;
+//!
+//! let tree = parse("(+ 1 2)").unwrap();
+//! assert_eq!(tree[0].as_form("+"), Some([&SExpr::from(1), &SExpr::from(2)]));
+//!
+//! let token: Token = Lexer::new("(").next().unwrap();
+//! assert_eq!((token.kind, token.span), (TokenKind::LParen, Span { start: 0, end: 1 }));
+//!
+//! let error: Error = parse(")").unwrap_err();
+//! assert_eq!(error.span, Span { start: 0, end: 1 });
+//! ```
+
+pub use crate::syntax::{parse, Error, Lexer, SExpr, Span, Token, TokenKind};
@@ -0,0 +1,368 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Support for Luna's interactive read-eval-print loop.
+
+use std::{
+  collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+  fs,
+  hash::{Hash, Hasher},
+  io::{self, Write},
+  path::Path,
+};
+
+use crate::syntax::{
+  self, format_source, line_col, parse, parse_all, ParseOptions, SExpr,
+};
+
+/// A handler for the evaluate and print steps of the REPL.
+///
+/// Embedders may implement this trait to customise how each line of input is evaluated
+/// and how its result is formatted.
+pub trait ReplHandler {
+  /// Handle a line of input, returning the response to print.
+  fn handle(&mut self, input: &str) -> String;
+}
+
+/// The default REPL handler, which parses each line and prints the resulting syntax
+/// tree in its debug representation.
+#[derive(Debug)]
+pub struct ParseHandler {
+  /// The cache of recent parse results.
+  cache: ParseCache,
+  /// Whether to print each line of input in the canonical style before its result.
+  echo: bool,
+}
+
+impl Default for ParseHandler {
+  fn default() -> Self {
+    Self { cache: ParseCache::new(64), echo: false }
+  }
+}
+
+impl ReplHandler for ParseHandler {
+  fn handle(&mut self, input: &str) -> String {
+    match MetaCommand::parse(input) {
+      Some(Ok(MetaCommand::Load(path))) => return load(path),
+      Some(Ok(MetaCommand::Echo(echo))) => {
+        self.echo = echo;
+        return format!("Echo {}", if echo { "enabled" } else { "disabled" });
+      },
+      Some(Err(message)) => return format!("Error: {message}"),
+      None => {},
+    }
+
+    // TODO: Properly display and format syntax trees.
+    match self.cache.parse(input) {
+      Ok(sexpr) if self.echo => match format_source(input) {
+        Ok(formatted) => format!("{formatted}{sexpr:?}"),
+        Err(_) => format!("{sexpr:?}"),
+      },
+      Ok(sexpr) => format!("{sexpr:?}"),
+      // TODO: Implement a unified error type with improved formatting.
+      Err(error) => {
+        // NOTE: The span is relative to the input after its byte order mark has been
+        //       stripped.
+        let source = ParseOptions::default().strip(input);
+        format!(
+          "Syntax error: {error}\ncontext: {}",
+          &source[error.span.start..error.span.end]
+        )
+      },
+    }
+  }
+}
+
+/// A command that is handled by the REPL itself rather than evaluated.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MetaCommand<'a> {
+  /// Load the program at the given path into the session, as in `:load path`.
+  ///
+  /// A relative path is resolved against the current working directory.
+  Load(&'a Path),
+  /// Enable or disable echoing each line of input in the canonical style before its
+  /// result, as in `:echo on` or `:echo off`.
+  Echo(bool),
+}
+
+impl<'a> MetaCommand<'a> {
+  /// Parse a line of input as a meta-command.
+  ///
+  /// This returns `None` if the input is not a meta-command, in which case it should
+  /// be evaluated as usual, or an error message if it is a malformed meta-command.
+  pub fn parse(input: &'a str) -> Option<Result<Self, String>> {
+    let input = input.trim();
+    let (command, argument) = match input.split_once(char::is_whitespace) {
+      Some((command, argument)) => (command, argument.trim_start()),
+      None => (input, ""),
+    };
+
+    // NOTE: Anything else is a keyword such as `:loader` or an ordinary expression.
+    match (command, argument) {
+      (":load", "") => Some(Err("expected a path after :load".to_string())),
+      (":load", path) => Some(Ok(MetaCommand::Load(Path::new(path)))),
+      (":echo", "on") => Some(Ok(MetaCommand::Echo(true))),
+      (":echo", "off") => Some(Ok(MetaCommand::Echo(false))),
+      (":echo", _) => Some(Err("expected `on` or `off` after :echo".to_string())),
+      _ => None,
+    }
+  }
+}
+
+/// Load the program at the given path, returning a summary of the result or a line for
+/// each syntax error that was encountered.
+fn load(path: &Path) -> String {
+  let input = match fs::read_to_string(path) {
+    Ok(input) => input,
+    Err(error) => return format!("Error: could not read {}: {error}", path.display()),
+  };
+
+  // TODO: Define the forms in the session once evaluation is implemented.
+  let (program, errors) = parse_all(&input);
+  if errors.is_empty() {
+    return format!("Loaded {} form(s) from {}", program.len(), path.display());
+  }
+
+  let (source, offset) = ParseOptions::default().display_source(&input);
+  let lines: Vec<_> = errors
+    .iter()
+    .map(|error| {
+      let (line, column) = line_col(source, error.span.start + offset);
+      format!("{}:{line}:{column}: {error}", path.display())
+    })
+    .collect();
+  lines.join("\n")
+}
+
+/// Run the REPL over a sequence of input lines, writing the response of the handler to
+/// each line to the given output.
+pub fn run(
+  handler: &mut impl ReplHandler,
+  lines: impl IntoIterator<Item = String>,
+  output: &mut impl Write,
+) -> io::Result<()> {
+  for line in lines {
+    writeln!(output, "{}", handler.handle(&line))?;
+  }
+
+  Ok(())
+}
+
+/// A small least-recently-used cache of parse results, keyed by a hash of the input.
+///
+/// This allows the REPL to avoid re-parsing lines that are submitted repeatedly, such
+/// as when they are recalled from the history.
+#[derive(Debug)]
+pub struct ParseCache {
+  /// The maximum number of entries to retain.
+  capacity: usize,
+  /// The cached inputs and their parse results, keyed by the hash of each input.
+  entries: HashMap<u64, (String, Result<Vec<SExpr>, syntax::Error>)>,
+  /// The hashes of the cached inputs, from least to most recently used.
+  order: VecDeque<u64>,
+}
+
+impl ParseCache {
+  /// Create a new, empty cache that retains at most `capacity` parse results.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `capacity` is zero.
+  pub fn new(capacity: usize) -> Self {
+    assert!(capacity > 0, "a parse cache must have a non-zero capacity");
+    Self { capacity, entries: HashMap::new(), order: VecDeque::new() }
+  }
+
+  /// Parse an input, reusing a cached result if the same input was parsed recently.
+  pub fn parse(&mut self, input: &str) -> &Result<Vec<SExpr>, syntax::Error> {
+    let key = hash(input);
+
+    // NOTE: Since distinct inputs may have the same hash, an entry is only treated as a
+    //       hit if its input is identical, and is otherwise replaced.
+    if !self.contains(input) {
+      if self.entries.remove(&key).is_none() && self.entries.len() >= self.capacity {
+        if let Some(oldest) = self.order.pop_front() {
+          self.entries.remove(&oldest);
+        }
+      }
+      self.entries.insert(key, (input.to_string(), parse(input)));
+    }
+    self.touch(key);
+
+    &self.entries[&key].1
+  }
+
+  /// Check whether the parse result for an input is cached.
+  pub fn contains(&self, input: &str) -> bool {
+    matches!(self.entries.get(&hash(input)), Some((cached, _)) if cached == input)
+  }
+
+  /// Get the number of cached parse results.
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  /// Check whether there are no cached parse results.
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Mark the entry with the given key as the most recently used.
+  fn touch(&mut self, key: u64) {
+    self.order.retain(|&other| other != key);
+    self.order.push_back(key);
+  }
+}
+
+/// Compute the hash of an input.
+fn hash(input: &str) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  input.hash(&mut hasher);
+  hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A handler that records its inputs and echoes them back in upper case.
+  #[derive(Default)]
+  struct MockHandler {
+    inputs: Vec<String>,
+  }
+
+  impl ReplHandler for MockHandler {
+    fn handle(&mut self, input: &str) -> String {
+      self.inputs.push(input.to_string());
+      input.to_uppercase()
+    }
+  }
+
+  #[test]
+  fn run_with_handler() -> io::Result<()> {
+    let mut handler = MockHandler::default();
+    let mut output = Vec::new();
+    run(&mut handler, ["(a b)", "", "c"].map(String::from), &mut output)?;
+
+    assert_eq!(handler.inputs, ["(a b)", "", "c"]);
+    assert_eq!(String::from_utf8_lossy(&output), "(A B)\n\nC\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_handler() {
+    let mut handler = ParseHandler::default();
+    assert_eq!(handler.handle("a"), format!("{:?}", parse("a").unwrap()));
+    assert_eq!(handler.handle("(a))"), "Syntax error: unexpected `)`\ncontext: )");
+    // The context should be taken from the input after its byte order mark.
+    assert_eq!(
+      handler.handle("\u{FEFF}(a))"),
+      "Syntax error: unexpected `)`\ncontext: )"
+    );
+    assert_eq!(
+      handler.handle("\u{FEFF}(λ))"),
+      "Syntax error: unexpected `)`\ncontext: )"
+    );
+  }
+
+  #[test]
+  fn echo_formatted_input() {
+    let mut handler = ParseHandler::default();
+    let input = "(  define   x\n  [f  'y] )";
+    let result = format!("{:?}", parse(input).unwrap());
+    assert_eq!(handler.handle(input), result);
+
+    assert_eq!(handler.handle(":echo on"), "Echo enabled");
+    assert_eq!(handler.handle(input), format!("(define x [f 'y])\n{result}"));
+    // Echoing should be skipped if the input cannot be parsed.
+    assert_eq!(handler.handle("(a))"), "Syntax error: unexpected `)`\ncontext: )");
+
+    assert_eq!(handler.handle(":echo off"), "Echo disabled");
+    assert_eq!(handler.handle(input), result);
+  }
+
+  #[test]
+  fn parse_meta_command() {
+    let load = |path| Some(Ok(MetaCommand::Load(Path::new(path))));
+    assert_eq!(MetaCommand::parse(":load foo.scm"), load("foo.scm"));
+    assert_eq!(MetaCommand::parse("  :load   a/b c.scm  "), load("a/b c.scm"));
+
+    let missing = Some(Err("expected a path after :load".to_string()));
+    assert_eq!(MetaCommand::parse(":load"), missing);
+    assert_eq!(MetaCommand::parse(":load   "), missing);
+
+    assert_eq!(MetaCommand::parse(":echo on"), Some(Ok(MetaCommand::Echo(true))));
+    assert_eq!(MetaCommand::parse(" :echo  off "), Some(Ok(MetaCommand::Echo(false))));
+    let invalid = Some(Err("expected `on` or `off` after :echo".to_string()));
+    assert_eq!(MetaCommand::parse(":echo"), invalid);
+    assert_eq!(MetaCommand::parse(":echo yes"), invalid);
+
+    // Anything else should be evaluated as usual.
+    assert_eq!(MetaCommand::parse(":loader"), None);
+    assert_eq!(MetaCommand::parse("(:load x)"), None);
+  }
+
+  #[test]
+  fn load_file() -> io::Result<()> {
+    let root = std::env::temp_dir().join(format!("luna-load-{}", std::process::id()));
+    fs::create_dir_all(&root)?;
+    let mut handler = ParseHandler::default();
+
+    let path = root.join("valid.scm");
+    fs::write(&path, "(define x 1)\n(f x)\n")?;
+    let response = handler.handle(&format!(":load {}", path.display()));
+    assert_eq!(response, format!("Loaded 2 form(s) from {}", path.display()));
+
+    let path = root.join("invalid.scm");
+    fs::write(&path, "(a)\n(b | c)\n")?;
+    let response = handler.handle(&format!(":load {}", path.display()));
+    assert_eq!(response, format!("{}:2:4: encountered invalid token", path.display()));
+
+    // Errors should be located within the file as written, including the shebang line.
+    let path = root.join("shebang.scm");
+    fs::write(&path, "\u{FEFF}#!/usr/bin/env luna\n(a))\n")?;
+    let response = handler.handle(&format!(":load {}", path.display()));
+    assert_eq!(response, format!("{}:2:4: unexpected `)`", path.display()));
+
+    let path = root.join("missing.scm");
+    let response = handler.handle(&format!(":load {}", path.display()));
+    assert!(response.starts_with(&format!("Error: could not read {}", path.display())));
+
+    fs::remove_dir_all(root)?;
+    Ok(())
+  }
+
+  #[test]
+  fn cache_hit_and_miss() {
+    let mut cache = ParseCache::new(2);
+    assert!(!cache.contains("(a b)"));
+
+    let result = cache.parse("(a b)").clone();
+    assert_eq!(result, parse("(a b)"));
+    assert!(cache.contains("(a b)"));
+    assert_eq!(cache.parse("(a b)"), &result);
+    assert_eq!(cache.len(), 1);
+
+    // Errors should be cached too.
+    assert!(cache.parse("(a b").is_err());
+    assert!(cache.contains("(a b"));
+    assert_eq!(cache.len(), 2);
+  }
+
+  #[test]
+  fn cache_eviction() {
+    let mut cache = ParseCache::new(2);
+
+    cache.parse("a");
+    cache.parse("b");
+    // Using `a` again should make `b` the least recently used entry.
+    cache.parse("a");
+    cache.parse("c");
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.contains("a"));
+    assert!(!cache.contains("b"));
+    assert!(cache.contains("c"));
+  }
+}
@@ -3,17 +3,65 @@
 
 //! Lexing and parsing of Luna source code.
 
+use std::fmt;
+
 use thiserror::Error;
 
+mod alpha;
+mod annotated;
+mod bracket;
+mod comment;
+mod diagnostic;
+mod export;
+mod format;
+mod hash;
+mod intern;
 mod lexer;
+mod literal;
 mod parser;
+mod pretty;
+mod schema;
+mod stats;
+mod stream;
+mod transform;
 
-pub use lexer::{Lexer, Token, TokenKind};
-pub use parser::{parse, SExpr};
+pub use alpha::alpha_eq;
+pub use annotated::{parse_annotated, Annotated, AnnotatedNode};
+pub use bracket::unbalanced_brackets;
+pub use comment::{comment_spans, extract_doc_comments, strip_comments};
+pub use diagnostic::{line_col, line_col_with, ColumnUnit, SyntaxErrors};
+pub use export::{export, ExportStyle};
+pub use format::{format_source, is_formatted};
+pub use hash::semantic_hash;
+pub use intern::{Interner, SymbolId};
+pub use lexer::{
+  token_at, Lexer, LexerOptions, Token, TokenKind, TokenWithLeadingTrivia, TriviaLexer,
+};
+#[cfg(feature = "iterative-parser")]
+pub use parser::parse_iterative;
+pub use parser::{
+  collect_symbols, parse, parse_all, parse_all_with_options, parse_data, parse_events,
+  parse_single, parse_upto, parse_with_options, split_forms, BoolStyle, DisplayConfig,
+  DisplayWith, Event, ParseOptions, Parser, ParserIter, SExpr, SymbolOccurrence,
+  SymbolStyle,
+};
+pub use pretty::pretty_ast;
+pub use schema::{Schema, SchemaError, SchemaErrorKind};
+pub use stats::{stats, Stats};
+pub use stream::{StreamLexer, StreamToken};
+pub use transform::{map_atoms, rename_symbols};
 
 /// A byte position within an input stream.
 pub type BytePos = usize;
 
+/// The largest byte position that a [`Span`] may refer to.
+///
+/// This is the size of the largest possible allocation, so every position within an
+/// input held in memory lies within it. Arithmetic on spans saturates at this bound
+/// rather than overflowing, and the parser rejects any larger input with an
+/// [`InputTooLarge`](ErrorKind::InputTooLarge) error.
+pub const MAX_POS: BytePos = isize::MAX as BytePos;
+
 /// A span of bytes within an input stream.
 ///
 /// Specifically, a `Span` is a range `[a, b)` for integers `a` and `b` such that `a <
@@ -26,12 +74,114 @@ pub struct Span {
   pub end: BytePos,
 }
 
+impl Span {
+  /// Shift this span by a signed number of bytes.
+  ///
+  /// This is useful for translating a span within a slice of some input into a span
+  /// within the whole input. Positions that would be shifted below zero are clamped to
+  /// zero, and those that would be shifted beyond [`MAX_POS`] are clamped to it.
+  pub fn shift(self, delta: isize) -> Span {
+    Span {
+      start: self.start.saturating_add_signed(delta).min(MAX_POS),
+      end: self.end.saturating_add_signed(delta).min(MAX_POS),
+    }
+  }
+
+  /// Shift this span forwards by an unsigned number of bytes.
+  ///
+  /// Unlike [`Span::shift`], this accepts any offset, including one too large to be
+  /// represented as an `isize`. Positions that would be shifted beyond [`MAX_POS`] are
+  /// clamped to it.
+  pub fn offset(self, offset: BytePos) -> Span {
+    Span {
+      start: self.start.saturating_add(offset).min(MAX_POS),
+      end: self.end.saturating_add(offset).min(MAX_POS),
+    }
+  }
+}
+
+impl fmt::Display for Span {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}..{}", self.start, self.end)
+  }
+}
+
+/// A kind of bracket that may delimit a list.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub enum BracketKind {
+  /// Round brackets `(` and `)`.
+  #[default]
+  Paren,
+  /// Square brackets `[` and `]`.
+  Square,
+  /// Curly brackets `{` and `}`.
+  Curly,
+}
+
+impl BracketKind {
+  /// Get the opening character for this kind of bracket.
+  pub fn opener(&self) -> char {
+    match self {
+      BracketKind::Paren => '(',
+      BracketKind::Square => '[',
+      BracketKind::Curly => '{',
+    }
+  }
+
+  /// Get the closing character for this kind of bracket.
+  pub fn closer(&self) -> char {
+    match self {
+      BracketKind::Paren => ')',
+      BracketKind::Square => ']',
+      BracketKind::Curly => '}',
+    }
+  }
+}
+
+/// The radix in which an integer literal is written.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub enum Radix {
+  /// Base 2, written with the prefix `#b`.
+  Binary,
+  /// Base 8, written with the prefix `#o`.
+  Octal,
+  /// Base 10, which is written without a prefix by default but may also be written
+  /// with the prefix `#d`.
+  #[default]
+  Decimal,
+  /// Base 16, written with the prefix `#x`.
+  Hexadecimal,
+}
+
+impl Radix {
+  /// Get the base of this radix.
+  pub fn base(&self) -> u32 {
+    match self {
+      Radix::Binary => 2,
+      Radix::Octal => 8,
+      Radix::Decimal => 10,
+      Radix::Hexadecimal => 16,
+    }
+  }
+
+  /// Get the radix with the given prefix character, such as `x` for `#x`.
+  pub fn from_prefix(prefix: char) -> Option<Radix> {
+    match prefix.to_ascii_lowercase() {
+      'b' => Some(Radix::Binary),
+      'o' => Some(Radix::Octal),
+      'd' => Some(Radix::Decimal),
+      'x' => Some(Radix::Hexadecimal),
+      _ => None,
+    }
+  }
+}
+
 /// A syntax error.
 ///
 /// This error struct is emitted by the [`parse`] function if it encounters a
 /// span of source code containing a syntactical error. The specific kind of error is
 /// denoted by the [`ErrorKind`] enum.
-#[derive(Copy, Clone, Eq, PartialEq, Error, Debug)]
+#[derive(Clone, Eq, PartialEq, Error, Debug)]
 #[error("{kind}")]
 pub struct Error {
   /// The span of source code in which this syntax error was encountered.
@@ -41,24 +191,138 @@ pub struct Error {
 }
 
 /// The kind of a syntax error.
-#[derive(Copy, Clone, Eq, PartialEq, Error, Debug)]
+#[derive(Clone, Eq, PartialEq, Error, Debug)]
 pub enum ErrorKind {
   /// An invalid token was encountered.
   #[error("encountered invalid token")]
   InvalidToken,
+  /// An input that should consist of exactly one datum contained either none or more
+  /// than one.
+  #[error("expected exactly one datum")]
+  ExpectedSingleDatum,
+  /// The input exceeded the maximum permitted size.
+  #[error("input exceeds the maximum size of {} bytes", .limit)]
+  InputTooLarge {
+    /// The maximum permitted size of the input in bytes.
+    limit: usize,
+  },
+  /// An invalid escape sequence was encountered within a string literal.
+  #[error("invalid escape sequence `{}`", .sequence)]
+  InvalidEscape {
+    /// The invalid escape sequence that was encountered.
+    sequence: String,
+  },
+  /// A here-doc string without a terminating line was encountered.
+  #[error("unterminated here-doc, expected a line consisting of `{}`", .tag)]
+  UnterminatedHereDoc {
+    /// The tag that should terminate the here-doc.
+    tag: String,
+  },
+  /// An invalid character literal was encountered.
+  #[error("invalid character literal `{}`", .found)]
+  InvalidCharacter {
+    /// The invalid character literal that was encountered.
+    found: String,
+  },
+  /// An integer literal that is out of range was encountered.
+  #[error("integer literal out of range")]
+  IntegerOverflow,
+  /// A tab character was encountered while tabs are forbidden.
+  #[error("encountered tab character")]
+  TabInSource,
+  /// A `#`-prefixed reader syntax form that is not yet supported was encountered.
+  #[error("reader syntax `{}` is not yet supported", .form)]
+  UnsupportedReaderSyntax {
+    /// The reader syntax form that was encountered.
+    form: String,
+  },
+  /// A symbol beginning with a character that is reserved for reader syntax, such as
+  /// `#foo`, was encountered.
+  #[error("symbol cannot start with `{}`, which is reserved for reader syntax", .ch)]
+  ReservedSymbolStart {
+    /// The reserved character that the symbol starts with.
+    ch: char,
+  },
+  /// A lone `#` that is followed by neither reader syntax nor a name was encountered.
+  ///
+  /// Note that the empty symbol may still be written as `||`.
+  #[error("expected reader syntax after `#`, found nothing")]
+  EmptySymbol,
+  /// A reference to a datum label that has not been defined was encountered.
+  #[error("reference to undefined datum label #{}#", .label)]
+  UndefinedLabel {
+    /// The undefined label.
+    label: u32,
+  },
+  /// The input ended in the middle of a form.
+  #[error("unexpected end of input, expected {}", .expected)]
+  UnexpectedEof {
+    /// A description of what was expected instead.
+    expected: &'static str,
+  },
+  /// Parsing stopped because the maximum number of errors was reached.
+  #[error("too many errors, stopping after {}", .limit)]
+  TooManyErrors {
+    /// The maximum number of errors that are reported.
+    limit: usize,
+  },
+  /// A token exceeded the maximum permitted length.
+  #[error("token exceeds the maximum length of {} bytes", .limit)]
+  TokenTooLong {
+    /// The maximum permitted length of a token in bytes.
+    limit: usize,
+  },
+  /// A list exceeded the maximum permitted number of elements.
+  #[error("list exceeds the maximum length of {} elements", .limit)]
+  ListTooLong {
+    /// The maximum permitted number of elements in a list.
+    limit: usize,
+  },
+  /// An unquote abbreviation was encountered outside of any quasiquote.
+  #[error("{} outside of a quasiquote", .found)]
+  UnquoteOutsideQuasiquote {
+    /// The unquote token that was encountered.
+    found: TokenKind,
+  },
+  /// An abbreviation such as `'datum` was encountered while reading data rather than
+  /// code.
+  #[error("unexpected {} abbreviation in data", .found)]
+  AbbreviationInData {
+    /// The abbreviation prefix that was encountered.
+    found: TokenKind,
+  },
+  /// A dot was encountered outside of a dotted pair.
+  #[error("unexpected `.` outside of a dotted pair")]
+  UnexpectedDot,
   /// An unexpected token was encountered.
   #[error("unexpected {}", .found)]
   UnexpectedToken {
     /// The unexpected token that was encountered.
     found: TokenKind,
   },
+  /// A map with an odd number of elements, and hence a key without a value, was
+  /// encountered.
+  #[error("expected a value for every key in map")]
+  OddMapEntries,
+  /// A bracket that is reserved for future syntax was encountered.
+  #[error("{} is reserved for future syntax", .found)]
+  ReservedBracket {
+    /// The reserved bracket that was encountered.
+    found: TokenKind,
+  },
   /// An unexpected kind of closing bracket was encountered.
+  ///
+  /// The span of the error is that of the offending closing bracket, and the span of
+  /// the opening bracket that it failed to match is carried alongside so that both
+  /// can be highlighted together.
   #[error("expected {} to close preceding {}, found {} instead", .expected, .expected.opener(), .found)]
   UnexpectedBracket {
     /// The kind of closing bracket that was expected.
     expected: TokenKind,
     /// The kind of closing bracket that was encountered.
     found: TokenKind,
+    /// The span of the opening bracket that was left unclosed.
+    opener: Span,
   },
   /// An opening bracket without its corresponding closing bracket was encountered.
   #[error("expected {} to close preceding {}", .expected, .expected.opener())]
@@ -67,3 +331,38 @@ pub enum ErrorKind {
     expected: TokenKind,
   },
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn shift_span() {
+    let span = Span { start: 3, end: 5 };
+
+    assert_eq!(span.shift(0), span);
+    assert_eq!(span.shift(4), Span { start: 7, end: 9 });
+    assert_eq!(span.shift(-2), Span { start: 1, end: 3 });
+    // Positions should be clamped at zero rather than underflowing.
+    assert_eq!(span.shift(-4), Span { start: 0, end: 1 });
+    assert_eq!(span.shift(-10), Span { start: 0, end: 0 });
+  }
+
+  #[test]
+  fn offset_span() {
+    let span = Span { start: 3, end: 5 };
+
+    assert_eq!(span.offset(0), span);
+    assert_eq!(span.offset(4), Span { start: 7, end: 9 });
+    // Positions should be clamped at the bound rather than overflowing.
+    let span = Span { start: MAX_POS - 3, end: MAX_POS - 1 };
+    assert_eq!(span.offset(2), Span { start: MAX_POS - 1, end: MAX_POS });
+    assert_eq!(span.offset(usize::MAX), Span { start: MAX_POS, end: MAX_POS });
+    assert_eq!(span.shift(isize::MAX), Span { start: MAX_POS, end: MAX_POS });
+  }
+
+  #[test]
+  fn display_span() {
+    assert_eq!(Span { start: 3, end: 5 }.to_string(), "3..5");
+  }
+}
@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Comparison of syntax trees modulo the renaming of bound variables.
+
+use crate::syntax::SExpr;
+
+/// Check whether two expressions are equal up to a consistent renaming of the variables
+/// that they bind, such as `(lambda (x) x)` and `(lambda (y) y)`.
+///
+/// This is a heuristic rather than a full analysis of binders, since nothing is known
+/// yet about which forms bind variables. Only the parameters of `lambda` forms are
+/// treated as binding, whether written as a list of symbols, as in `(lambda (x y)
+/// ...)`, or as a single symbol, as in `(lambda args ...)`. A bound symbol is equal to
+/// another if both refer to the same binder, and a free symbol is only equal to a free
+/// symbol with the same name. Everything else is compared as with [`SExpr::value_eq`].
+pub fn alpha_eq(lhs: &SExpr, rhs: &SExpr) -> bool {
+  alpha_eq_in(lhs, rhs, &mut Vec::new())
+}
+
+/// Check whether two expressions are alpha-equivalent within an environment of the
+/// variables bound by enclosing forms, from outermost to innermost.
+fn alpha_eq_in<'a>(
+  lhs: &'a SExpr,
+  rhs: &'a SExpr,
+  env: &mut Vec<(&'a str, &'a str)>,
+) -> bool {
+  match (lhs, rhs) {
+    (SExpr::Symbol(lhs), SExpr::Symbol(rhs)) => {
+      let lhs_binder = env.iter().rposition(|&(name, _)| name == lhs);
+      let rhs_binder = env.iter().rposition(|&(_, name)| name == rhs);
+      match (lhs_binder, rhs_binder) {
+        (None, None) => lhs == rhs,
+        (lhs_binder, rhs_binder) => lhs_binder == rhs_binder,
+      }
+    },
+    (SExpr::List(lhs_list, _), SExpr::List(rhs_list, _)) => {
+      if let (Some((lhs_params, lhs_body)), Some((rhs_params, rhs_body))) =
+        (as_lambda(lhs), as_lambda(rhs))
+      {
+        if lhs_params.len() != rhs_params.len() || lhs_body.len() != rhs_body.len() {
+          return false;
+        }
+
+        let depth = env.len();
+        env.extend(lhs_params.into_iter().zip(rhs_params));
+        let eq =
+          lhs_body.iter().zip(rhs_body).all(|(lhs, rhs)| alpha_eq_in(lhs, rhs, env));
+        env.truncate(depth);
+        return eq;
+      }
+
+      lhs_list.len() == rhs_list.len()
+        && lhs_list.iter().zip(rhs_list).all(|(lhs, rhs)| alpha_eq_in(lhs, rhs, env))
+    },
+    (SExpr::Map(lhs), SExpr::Map(rhs)) => {
+      lhs.len() == rhs.len()
+        && lhs.iter().zip(rhs).all(|((lhs_key, lhs_value), (rhs_key, rhs_value))| {
+          alpha_eq_in(lhs_key, rhs_key, env) && alpha_eq_in(lhs_value, rhs_value, env)
+        })
+    },
+    (SExpr::Labelled(lhs_label, lhs), SExpr::Labelled(rhs_label, rhs)) => {
+      lhs_label == rhs_label && alpha_eq_in(lhs, rhs, env)
+    },
+    _ => lhs.value_eq(rhs),
+  }
+}
+
+/// Destructure an expression if it is a `lambda` form, returning the names of its
+/// parameters and the expressions of its body.
+///
+/// A form whose parameters are neither a symbol nor a list of symbols is not treated as
+/// a `lambda` form.
+fn as_lambda(sexpr: &SExpr) -> Option<(Vec<&str>, &[SExpr])> {
+  let [head, params, body @ ..] = sexpr.as_list()? else { return None };
+  if head.as_symbol() != Some("lambda") {
+    return None;
+  }
+
+  let params = match params {
+    SExpr::Symbol(name) => vec![name.as_str()],
+    SExpr::List(params, _) => {
+      params.iter().map(SExpr::as_symbol).collect::<Option<_>>()?
+    },
+    _ => return None,
+  };
+
+  Some((params, body))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse_single, Error};
+
+  fn check(lhs: &str, rhs: &str) -> Result<bool, Error> {
+    Ok(alpha_eq(&parse_single(lhs)?, &parse_single(rhs)?))
+  }
+
+  #[test]
+  fn rename_parameters() -> Result<(), Error> {
+    assert!(check("(lambda (x) x)", "(lambda (y) y)")?);
+    assert!(check("(lambda (x y) (f y x))", "(lambda (a b) (f b a))")?);
+    assert!(check("(lambda args (apply f args))", "(lambda xs (apply f xs))")?);
+    assert!(check(
+      "(lambda (x) (lambda (y) (x y)))",
+      "(lambda (y) (lambda (x) (y x)))"
+    )?);
+    // Shadowed parameters should refer to the innermost binder.
+    assert!(check("(lambda (x) (lambda (x) x))", "(lambda (a) (lambda (b) b))")?);
+    assert!(!check("(lambda (x) (lambda (x) x))", "(lambda (a) (lambda (b) a))")?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn distinguish_variables() -> Result<(), Error> {
+    // The renaming must be consistent.
+    assert!(!check("(lambda (x y) x)", "(lambda (a b) b)")?);
+    // Free variables must have the same name.
+    assert!(!check("(lambda (x) y)", "(lambda (x) z)")?);
+    assert!(!check("(lambda (x) y)", "(lambda (y) y)")?);
+    assert!(check("(f x)", "(f x)")?);
+    assert!(!check("(f x)", "(f y)")?);
+    assert!(!check("(lambda (x) x)", "(lambda (x y) x)")?);
+    // Only lambda parameters are treated as binders.
+    assert!(!check("(let ((x 1)) x)", "(let ((y 1)) y)")?);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,209 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Syntax trees whose nodes carry arbitrary annotations.
+
+use crate::syntax::{parse_events, BracketKind, BytePos, Error, Event, SExpr, Span};
+
+/// A symbolic expression in which every node carries an annotation of type `T`.
+///
+/// This allows passes over a syntax tree to decorate its nodes with information such
+/// as types, evaluation results, or source provenance without changing [`SExpr`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct Annotated<T> {
+  /// The annotation of this node.
+  pub meta: T,
+  /// The node itself.
+  pub node: AnnotatedNode<T>,
+}
+
+/// A node of an [`Annotated`] syntax tree.
+#[derive(Clone, PartialEq, Debug)]
+pub enum AnnotatedNode<T> {
+  /// An atom, which is never a list, map, or labelled datum.
+  Atom(SExpr),
+  /// A list of annotated expressions, along with the kind of bracket that delimited it.
+  List(Vec<Annotated<T>>, BracketKind),
+  /// A map of annotated keys to annotated values.
+  Map(Vec<(Annotated<T>, Annotated<T>)>),
+  /// An annotated datum labelled with `#n=`.
+  Labelled(u32, Box<Annotated<T>>),
+}
+
+impl<T> Annotated<T> {
+  /// Annotate every node of a symbolic expression with the result of a function.
+  ///
+  /// The function is called on each node before its children.
+  pub fn new(sexpr: SExpr, mut f: impl FnMut(&SExpr) -> T) -> Self {
+    fn go<T>(sexpr: SExpr, f: &mut impl FnMut(&SExpr) -> T) -> Annotated<T> {
+      let meta = f(&sexpr);
+      let node = match sexpr {
+        SExpr::List(list, bracket) => AnnotatedNode::List(
+          list.into_iter().map(|sexpr| go(sexpr, f)).collect(),
+          bracket,
+        ),
+        SExpr::Map(entries) => AnnotatedNode::Map(
+          entries.into_iter().map(|(key, value)| (go(key, f), go(value, f))).collect(),
+        ),
+        SExpr::Labelled(label, datum) => {
+          AnnotatedNode::Labelled(label, Box::new(go(*datum, f)))
+        },
+        atom => AnnotatedNode::Atom(atom),
+      };
+
+      Annotated { meta, node }
+    }
+
+    go(sexpr, &mut f)
+  }
+
+  /// Replace the annotation of every node with the result of a function.
+  pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Annotated<U> {
+    fn go<T, U>(annotated: Annotated<T>, f: &mut impl FnMut(T) -> U) -> Annotated<U> {
+      let meta = f(annotated.meta);
+      let node = match annotated.node {
+        AnnotatedNode::Atom(atom) => AnnotatedNode::Atom(atom),
+        AnnotatedNode::List(list, bracket) => AnnotatedNode::List(
+          list.into_iter().map(|child| go(child, f)).collect(),
+          bracket,
+        ),
+        AnnotatedNode::Map(entries) => AnnotatedNode::Map(
+          entries.into_iter().map(|(key, value)| (go(key, f), go(value, f))).collect(),
+        ),
+        AnnotatedNode::Labelled(label, datum) => {
+          AnnotatedNode::Labelled(label, Box::new(go(*datum, f)))
+        },
+      };
+
+      Annotated { meta, node }
+    }
+
+    go(self, &mut f)
+  }
+
+  /// Discard every annotation, recovering the underlying symbolic expression.
+  pub fn into_sexpr(self) -> SExpr {
+    match self.node {
+      AnnotatedNode::Atom(atom) => atom,
+      AnnotatedNode::List(list, bracket) => {
+        SExpr::List(list.into_iter().map(Annotated::into_sexpr).collect(), bracket)
+      },
+      AnnotatedNode::Map(entries) => SExpr::Map(
+        entries
+          .into_iter()
+          .map(|(key, value)| (key.into_sexpr(), value.into_sexpr()))
+          .collect(),
+      ),
+      AnnotatedNode::Labelled(label, datum) => {
+        SExpr::Labelled(label, Box::new(datum.into_sexpr()))
+      },
+    }
+  }
+}
+
+/// Parse source code into syntax trees whose every node is annotated with its span.
+///
+/// The span of a list covers its brackets, while that of an abbreviation such as
+/// `'datum` covers the prefix and the datum, and that of a labelled datum covers the
+/// label and the datum. As with [`parse`](crate::syntax::parse), spans are relative to
+/// the input after its byte order mark and shebang line have been stripped.
+pub fn parse_annotated(input: &str) -> Result<Vec<Annotated<Span>>, Error> {
+  /// A list that is under construction.
+  struct Frame {
+    /// The position at which the list starts.
+    start: BytePos,
+    /// The kind of bracket that delimits the list.
+    bracket: BracketKind,
+    /// The elements of the list that have been parsed so far.
+    elements: Vec<Annotated<Span>>,
+    /// The labels that were defined for the list, along with their positions.
+    labels: Vec<(u32, BytePos)>,
+  }
+
+  /// Wrap a datum in the labels that were defined for it, from innermost to outermost.
+  fn wrap(datum: Annotated<Span>, labels: Vec<(u32, BytePos)>) -> Annotated<Span> {
+    labels.into_iter().rev().fold(datum, |datum, (label, start)| Annotated {
+      meta: Span { start, end: datum.meta.end },
+      node: AnnotatedNode::Labelled(label, Box::new(datum)),
+    })
+  }
+
+  // NOTE: The bottom frame holds the top-level data.
+  let mut frames = vec![Frame {
+    start: 0,
+    bracket: BracketKind::Paren,
+    elements: Vec::new(),
+    labels: Vec::new(),
+  }];
+  let mut labels = Vec::new();
+  parse_events(input, &mut |event| match event {
+    Event::StartList(bracket, span) => frames.push(Frame {
+      start: span.start,
+      bracket,
+      elements: Vec::new(),
+      labels: std::mem::take(&mut labels),
+    }),
+    Event::Atom(atom, span) => {
+      let datum = Annotated { meta: span, node: AnnotatedNode::Atom(atom) };
+      let datum = wrap(datum, std::mem::take(&mut labels));
+      frames.last_mut().unwrap().elements.push(datum);
+    },
+    Event::EndList(span) => {
+      let frame = frames.pop().unwrap();
+      let list = Annotated {
+        meta: Span { start: frame.start, end: span.end },
+        node: AnnotatedNode::List(frame.elements, frame.bracket),
+      };
+      frames.last_mut().unwrap().elements.push(wrap(list, frame.labels));
+    },
+    Event::Label(label, span) => labels.push((label, span.start)),
+  })?;
+
+  Ok(frames.pop().unwrap().elements)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse, Error};
+
+  #[test]
+  fn annotate_sizes() -> Result<(), Error> {
+    let sexpr = parse("(a (b c))")?.remove(0);
+    let annotated = Annotated::new(sexpr.clone(), |sexpr| sexpr.to_string().len());
+
+    assert_eq!(annotated.meta, 9);
+    let AnnotatedNode::List(children, _) = &annotated.node else {
+      panic!("expected a list")
+    };
+    assert_eq!(children[0].meta, 1);
+    assert_eq!(children[0].node, AnnotatedNode::Atom(SExpr::from("a")));
+    assert_eq!(children[1].meta, 5);
+
+    let annotated = annotated.map(|size| size * 2);
+    assert_eq!(annotated.meta, 18);
+    assert_eq!(annotated.into_sexpr(), sexpr);
+
+    Ok(())
+  }
+
+  #[test]
+  fn annotate_spans() -> Result<(), Error> {
+    let span = |start, end| Span { start, end };
+
+    let tree = parse_annotated("(a [b]) 'c #0=d")?;
+    let spans: Vec<_> = tree.iter().map(|datum| datum.meta).collect();
+    assert_eq!(spans, [span(0, 7), span(8, 10), span(11, 15)]);
+
+    let AnnotatedNode::List(children, _) = &tree[0].node else {
+      panic!("expected a list")
+    };
+    let spans: Vec<_> = children.iter().map(|child| child.meta).collect();
+    assert_eq!(spans, [span(1, 2), span(3, 6)]);
+
+    let trees: Vec<_> = tree.into_iter().map(Annotated::into_sexpr).collect();
+    assert_eq!(trees, parse("(a [b]) 'c #0=d")?);
+
+    Ok(())
+  }
+}
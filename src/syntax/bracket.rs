@@ -0,0 +1,69 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Analysis of the brackets within source code.
+
+use crate::syntax::{Lexer, Span, Token};
+
+/// Get the span of every bracket within source code that lacks a partner, in order of
+/// appearance.
+///
+/// These are the opening brackets that are never closed and the closing brackets that
+/// do not close the most recently opened bracket. In the case of a mismatch such as
+/// `(]`, only the closing bracket is stray, so the opening bracket may still be closed
+/// by a later bracket of the right kind. Since this only lexes the source code, it
+/// succeeds even if the source code is not otherwise a syntactically valid program.
+pub fn unbalanced_brackets(input: &str) -> Vec<Span> {
+  let mut openers: Vec<Token> = Vec::new();
+  let mut unbalanced = Vec::new();
+  for token in Lexer::new(input) {
+    if token.kind.is_opener() {
+      openers.push(token);
+    } else if token.kind.is_closer() {
+      match openers.last() {
+        Some(opener) if opener.kind.closer() == token.kind => {
+          openers.pop();
+        },
+        _ => unbalanced.push(token.span),
+      }
+    }
+  }
+
+  unbalanced.extend(openers.into_iter().map(|opener| opener.span));
+  unbalanced.sort_by_key(|span| span.start);
+  unbalanced
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn span(start: usize, end: usize) -> Span {
+    Span { start, end }
+  }
+
+  #[test]
+  fn balanced() {
+    assert!(unbalanced_brackets("").is_empty());
+    assert!(unbalanced_brackets("(a [b {c}] \")\" #\\( ; )\n)").is_empty());
+  }
+
+  #[test]
+  fn unclosed_opener() {
+    assert_eq!(unbalanced_brackets("(a (b)"), [span(0, 1)]);
+    assert_eq!(unbalanced_brackets("[(a"), [span(0, 1), span(1, 2)]);
+  }
+
+  #[test]
+  fn stray_closer() {
+    assert_eq!(unbalanced_brackets("(a) b)"), [span(5, 6)]);
+    assert_eq!(unbalanced_brackets("}"), [span(0, 1)]);
+  }
+
+  #[test]
+  fn mismatched_closer() {
+    assert_eq!(unbalanced_brackets("(]"), [span(0, 1), span(1, 2)]);
+    // The opener may still be closed by a later closer of the right kind.
+    assert_eq!(unbalanced_brackets("(a ] b)"), [span(3, 4)]);
+  }
+}
@@ -0,0 +1,141 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Extraction of comments from source code.
+
+use crate::syntax::{Lexer, LexerOptions, Span, Token, TokenKind};
+
+/// Get the span of every comment within source code, in order of appearance.
+///
+/// Since this only lexes the source code, it succeeds even if the source code is not a
+/// syntactically valid program. The line ending that terminates a line comment is not
+/// included in its span. Datum comments are not included, since they comment out a
+/// datum rather than text.
+pub fn comment_spans(input: &str) -> Vec<Span> {
+  comments(input).map(|comment| comment.span).collect()
+}
+
+/// Remove every comment from source code, leaving everything else intact.
+///
+/// Only the text of each comment is removed, so the line ending that terminates a line
+/// comment is kept, as is any whitespace before it. Semicolons within strings and
+/// character literals are not comments and so are also kept, as are datum comments,
+/// since they comment out a datum rather than text. As with [`comment_spans`], this
+/// succeeds even if the source code is not a syntactically valid program.
+pub fn strip_comments(input: &str) -> String {
+  let mut output = String::with_capacity(input.len());
+  let mut end = 0;
+  for comment in comments(input) {
+    output.push_str(&input[end..comment.span.start]);
+    end = comment.span.end;
+  }
+  output.push_str(&input[end..]);
+
+  output
+}
+
+/// Extract every block of contiguous line comments from source code, along with its
+/// span and text.
+///
+/// A block consists of line comments on consecutive lines with nothing else between
+/// them. The text of a block is the text of each of its comments with the leading
+/// semicolons and a single following space stripped, joined by newlines. A comment that
+/// follows code on the same line always starts a new block.
+pub fn extract_doc_comments(input: &str) -> Vec<(Span, String)> {
+  let mut blocks: Vec<(Span, String)> = Vec::new();
+  let mut previous_end = None;
+  for comment in comments(input) {
+    let text = comment.lexeme.trim_start_matches(';');
+    let text = text.strip_prefix(' ').unwrap_or(text);
+
+    // NOTE: A comment continues the previous block only if they are separated by a
+    //       single line ending and any other whitespace.
+    let continues = previous_end.is_some_and(|end| {
+      let gap = &input[end..comment.span.start];
+      gap.trim().is_empty() && gap.matches('\n').count() == 1
+    });
+    match blocks.last_mut() {
+      Some((span, block)) if continues => {
+        span.end = comment.span.end;
+        block.push('\n');
+        block.push_str(text);
+      },
+      _ => blocks.push((comment.span, text.to_string())),
+    }
+    previous_end = Some(comment.span.end);
+  }
+
+  blocks
+}
+
+/// Get an iterator over the comment tokens within source code.
+fn comments(input: &str) -> impl Iterator<Item = Token<'_>> {
+  let options = LexerOptions { emit_comments: true, ..LexerOptions::default() };
+  Lexer::with_options(input, &options).filter(|token| token.kind == TokenKind::Comment)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn line_comment_spans() {
+    assert_eq!(
+      comment_spans("; a\r\n;; b"),
+      [Span { start: 0, end: 3 }, Span { start: 5, end: 9 }]
+    );
+    assert!(comment_spans("").is_empty());
+  }
+
+  #[test]
+  fn mixed_comment_spans() {
+    // Neither semicolons within strings nor datum comments are line comments.
+    assert_eq!(
+      comment_spans("(a \"; b\" #;c) ; d\n(e ; f"),
+      [Span { start: 14, end: 17 }, Span { start: 21, end: 24 }],
+    );
+  }
+
+  #[test]
+  fn strip_all_comments() {
+    assert_eq!(strip_comments("; a\r\n(b ; c\n d);;e"), "\r\n(b \n d)");
+    assert_eq!(strip_comments(""), "");
+    assert_eq!(strip_comments("(a\tb)"), "(a\tb)");
+
+    // Comment-like content that is not a comment should be preserved.
+    let input = "(display \"; not a comment\") #\\; #;(x) \"a\\\"; b\"";
+    assert_eq!(strip_comments(input), input);
+    assert_eq!(strip_comments("\"a;b\" ; c"), "\"a;b\" ");
+  }
+
+  #[test]
+  fn extract_two_blocks() {
+    const INPUT: &str = concat!(
+      ";;; A module.\n",
+      ";;;\n",
+      ";;; It does things.\n",
+      "(import (scheme base))\n",
+      "; Compute the factorial of n.\n",
+      ";   (Recursively.)\n",
+      "(define (fac n) ...)\n",
+    );
+
+    assert_eq!(
+      extract_doc_comments(INPUT),
+      [
+        (Span { start: 0, end: 37 }, "A module.\n\nIt does things.".to_string()),
+        (
+          Span { start: 61, end: 109 },
+          "Compute the factorial of n.\n  (Recursively.)".to_string(),
+        ),
+      ],
+    );
+  }
+
+  #[test]
+  fn separate_blocks() {
+    let blocks = extract_doc_comments("; a\n\n; b\nx ; c\n; d");
+    let texts: Vec<_> = blocks.iter().map(|(_, text)| text.as_str()).collect();
+    assert_eq!(texts, ["a", "b", "c\nd"]);
+  }
+}
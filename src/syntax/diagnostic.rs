@@ -0,0 +1,493 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Reporting of syntax errors in terms of their location in source code.
+
+use std::fmt;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::syntax::{BytePos, Error, ErrorKind, Span};
+
+/// The unit in which columns are counted.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub enum ColumnUnit {
+  /// Unicode scalar values, which is simple but misaligns carets beneath combining
+  /// sequences and wide characters.
+  #[default]
+  Scalar,
+  /// Extended grapheme clusters, so that a combining sequence such as `e\u{301}`
+  /// occupies a single column.
+  Grapheme,
+  /// Columns of a terminal, so that each grapheme cluster occupies its display width
+  /// and hence a wide character such as `Ａ` occupies two columns.
+  Width,
+}
+
+impl ColumnUnit {
+  /// Get the number of columns that a string occupies in this unit.
+  fn measure(self, text: &str) -> usize {
+    match self {
+      ColumnUnit::Scalar => text.chars().count(),
+      ColumnUnit::Grapheme => text.graphemes(true).count(),
+      ColumnUnit::Width => text.graphemes(true).map(UnicodeWidthStr::width).sum(),
+    }
+  }
+}
+
+/// Get the line and column numbers of a byte position within an input string.
+///
+/// Both numbers start at 1, and columns are counted in Unicode scalar values. A
+/// position past the end of the input is clamped to the end of the input.
+pub fn line_col(input: &str, pos: BytePos) -> (usize, usize) {
+  line_col_with(input, pos, ColumnUnit::Scalar)
+}
+
+/// Get the line and column numbers of a byte position within an input string, counting
+/// columns in the given unit.
+///
+/// As with [`line_col`], both numbers start at 1, and a position past the end of the
+/// input is clamped to the end of the input.
+pub fn line_col_with(input: &str, pos: BytePos, unit: ColumnUnit) -> (usize, usize) {
+  let mut pos = pos.min(input.len());
+  // Back up to the nearest character boundary so that we never slice into the middle
+  // of a multi-byte character.
+  while !input.is_char_boundary(pos) {
+    pos -= 1;
+  }
+
+  let before = &input[..pos];
+  let line = before.matches('\n').count() + 1;
+  let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+  let column = unit.measure(&before[line_start..]) + 1;
+
+  (line, column)
+}
+
+/// Find the line of source code that contains the start of a span, along with the
+/// width of the span in the given unit.
+///
+/// Spans that continue past the end of their first line are cut off at the end of
+/// that line, and empty spans are given a width of one.
+fn span_line(input: &str, span: Span, unit: ColumnUnit) -> (&str, usize) {
+  let start = span.start.min(input.len());
+  let line_start = input[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+  let line_end = input[start..].find('\n').map(|i| start + i).unwrap_or(input.len());
+  let source_line = input[line_start..line_end].trim_end_matches('\r');
+
+  let end = span.end.clamp(start, line_start + source_line.len());
+  (source_line, unit.measure(&input[start..end]).max(1))
+}
+
+impl Error {
+  /// Get a terse, one-line summary of this error, such as `unmatched bracket`.
+  ///
+  /// Unlike the [`Display`](fmt::Display) implementation, the summary omits any details
+  /// specific to this occurrence of the error, so it is suited to status bars and
+  /// compact logs.
+  pub fn summary(&self) -> String {
+    let summary = match self.kind {
+      ErrorKind::InvalidToken => "invalid token",
+      ErrorKind::ExpectedSingleDatum => "expected one datum",
+      ErrorKind::InputTooLarge { .. } => "input too large",
+      ErrorKind::InvalidEscape { .. } => "invalid escape",
+      ErrorKind::UnterminatedHereDoc { .. } => "unterminated here-doc",
+      ErrorKind::InvalidCharacter { .. } => "invalid character",
+      ErrorKind::IntegerOverflow => "integer overflow",
+      ErrorKind::TabInSource => "tab character",
+      ErrorKind::UnsupportedReaderSyntax { .. } => "unsupported reader syntax",
+      ErrorKind::ReservedSymbolStart { .. } => "reserved symbol start",
+      ErrorKind::EmptySymbol => "empty symbol",
+      ErrorKind::UndefinedLabel { .. } => "undefined label",
+      ErrorKind::UnexpectedEof { .. } => "unexpected end of input",
+      ErrorKind::TooManyErrors { .. } => "too many errors",
+      ErrorKind::TokenTooLong { .. } => "token too long",
+      ErrorKind::ListTooLong { .. } => "list too long",
+      ErrorKind::UnquoteOutsideQuasiquote { .. } => "unquote outside quasiquote",
+      ErrorKind::AbbreviationInData { .. } => "abbreviation in data",
+      ErrorKind::UnexpectedDot => "unexpected dot",
+      ErrorKind::UnexpectedToken { .. } => "unexpected token",
+      ErrorKind::OddMapEntries => "missing map value",
+      ErrorKind::ReservedBracket { .. } => "reserved bracket",
+      ErrorKind::UnexpectedBracket { .. } => "mismatched bracket",
+      ErrorKind::UnmatchedBracket { .. } => "unmatched bracket",
+    };
+
+    summary.to_string()
+  }
+
+  /// Render this error in the style of a `rustc` diagnostic.
+  ///
+  /// The rendered diagnostic consists of a header containing the error message, the
+  /// location of the error within the named file, and the offending line of source
+  /// code with the span of the error underlined. Spans that continue past the end of
+  /// their first line are only underlined up to the end of that line. For a mismatched
+  /// closing bracket, the opening bracket that it fails to close is also labelled.
+  pub fn render_rustc(&self, input: &str, filename: &str) -> String {
+    self.render_rustc_with(input, filename, ColumnUnit::Scalar)
+  }
+
+  /// Render this error in the style of a `rustc` diagnostic as with
+  /// [`Error::render_rustc`], counting columns in the given unit.
+  ///
+  /// The unit determines both the reported column and the alignment of the carets, so
+  /// [`ColumnUnit::Width`] aligns them correctly within a terminal.
+  pub fn render_rustc_with(
+    &self,
+    input: &str,
+    filename: &str,
+    unit: ColumnUnit,
+  ) -> String {
+    let (line, column) = line_col_with(input, self.span.start, unit);
+    let (source_line, width) = span_line(input, self.span, unit);
+    let padding = " ".repeat(column - 1);
+    let carets = "^".repeat(width);
+    let gutter = " ".repeat(line.to_string().len());
+
+    let mut output = format!("error: {self}\n");
+    output += &format!("{gutter}--> {filename}:{line}:{column}\n");
+    output += &format!("{gutter} |\n");
+
+    // A mismatched closing bracket is reported at the closer, so the bracket that it
+    // fails to close is labelled as well.
+    let opener = match self.kind {
+      ErrorKind::UnexpectedBracket { opener, .. } if opener.end <= self.span.start => {
+        Some(opener)
+      },
+      _ => None,
+    };
+
+    match opener {
+      Some(opener) => {
+        let (opener_line, opener_column) = line_col_with(input, opener.start, unit);
+        let (opener_source, opener_width) = span_line(input, opener, unit);
+        let opener_padding = " ".repeat(opener_column - 1);
+        let dashes = "-".repeat(opener_width);
+
+        if opener_line == line {
+          let between = " ".repeat(column - opener_column - opener_width);
+          output += &format!("{line} | {source_line}\n");
+          output += &format!("{gutter} | {opener_padding}{dashes}{between}{carets}\n");
+          output += &format!("{gutter} | {opener_padding}|\n");
+          output += &format!("{gutter} | {opener_padding}opened here\n");
+        } else {
+          let width = gutter.len();
+          output += &format!("{opener_line:>width$} | {opener_source}\n");
+          output += &format!("{gutter} | {opener_padding}{dashes} opened here\n");
+          if opener_line + 1 < line {
+            output += "...\n";
+          }
+          output += &format!("{line} | {source_line}\n");
+          output += &format!("{gutter} | {padding}{carets}\n");
+        }
+      },
+      None => {
+        output += &format!("{line} | {source_line}\n");
+        output += &format!("{gutter} | {padding}{carets}\n");
+      },
+    }
+
+    output
+  }
+}
+
+/// A collection of syntax errors encountered within the same input.
+///
+/// Since the errors are displayed with their line and column numbers, these are
+/// computed from the input upon construction.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SyntaxErrors {
+  /// The contained errors, each paired with the line and column at which it starts.
+  errors: Vec<(Error, (usize, usize))>,
+}
+
+impl SyntaxErrors {
+  /// Create a new collection of syntax errors that were encountered within an input.
+  pub fn new(input: &str, errors: Vec<Error>) -> Self {
+    let errors =
+      errors.into_iter().map(|error| (error.clone(), line_col(input, error.span.start)));
+    Self { errors: errors.collect() }
+  }
+
+  /// Get an iterator over the contained errors.
+  pub fn iter(&self) -> impl Iterator<Item = &Error> {
+    self.errors.iter().map(|(error, _)| error)
+  }
+
+  /// Get the number of contained errors.
+  pub fn len(&self) -> usize {
+    self.errors.len()
+  }
+
+  /// Check whether there are no contained errors.
+  pub fn is_empty(&self) -> bool {
+    self.errors.is_empty()
+  }
+}
+
+impl fmt::Display for SyntaxErrors {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (i, (error, (line, column))) in self.errors.iter().enumerate() {
+      if i > 0 {
+        writeln!(f)?;
+      }
+      write!(f, "{line}:{column}: {error}")?;
+    }
+
+    Ok(())
+  }
+}
+
+impl std::error::Error for SyntaxErrors {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse, TokenKind};
+
+  #[test]
+  fn compute_line_col() {
+    const INPUT: &str = "(a\n  λb)\nc";
+
+    assert_eq!(line_col(INPUT, 0), (1, 1));
+    assert_eq!(line_col(INPUT, 2), (1, 3));
+    assert_eq!(line_col(INPUT, 3), (2, 1));
+    // Columns are counted in characters rather than bytes.
+    assert_eq!(line_col(INPUT, 7), (2, 4));
+    assert_eq!(line_col(INPUT, INPUT.len()), (3, 2));
+    assert_eq!(line_col(INPUT, 100), (3, 2));
+  }
+
+  #[test]
+  fn count_columns() {
+    // An `e` followed by a combining acute accent, and two full-width `Ａ`s.
+    const INPUT: &str = "(e\u{301} \u{ff21}\u{ff21} x)";
+    let pos = INPUT.find('x').unwrap();
+
+    assert_eq!(line_col(INPUT, pos), (1, 8));
+    assert_eq!(line_col_with(INPUT, pos, ColumnUnit::Scalar), (1, 8));
+    assert_eq!(line_col_with(INPUT, pos, ColumnUnit::Grapheme), (1, 7));
+    assert_eq!(line_col_with(INPUT, pos, ColumnUnit::Width), (1, 9));
+    assert_eq!(line_col_with(INPUT, 1, ColumnUnit::Width), (1, 2));
+    assert_eq!(line_col_with(INPUT, 5, ColumnUnit::Width), (1, 4));
+  }
+
+  #[test]
+  fn render_rustc_width() {
+    const INPUT: &str = "(\u{ff21}\u{ff22} e\u{301}#)";
+
+    let error =
+      Error { span: Span { start: 11, end: 12 }, kind: ErrorKind::InvalidToken };
+    assert_eq!(
+      error.render_rustc_with(INPUT, "test.scm", ColumnUnit::Width),
+      concat!(
+        "error: encountered invalid token\n",
+        " --> test.scm:1:8\n",
+        "  |\n",
+        "1 | (\u{ff21}\u{ff22} e\u{301}#)\n",
+        "  |        ^\n",
+      ),
+    );
+
+    let error = Error { span: Span { start: 1, end: 7 }, kind: ErrorKind::InvalidToken };
+    let rendered = error.render_rustc_with(INPUT, "test.scm", ColumnUnit::Width);
+    assert!(rendered.ends_with("  |  ^^^^\n"));
+  }
+
+  #[test]
+  fn summarise_errors() {
+    use TokenKind::*;
+
+    let string = |s: &str| s.to_string();
+    let cases = [
+      (ErrorKind::InvalidToken, "invalid token", "encountered invalid token"),
+      (
+        ErrorKind::ExpectedSingleDatum,
+        "expected one datum",
+        "expected exactly one datum",
+      ),
+      (
+        ErrorKind::InputTooLarge { limit: 8 },
+        "input too large",
+        "input exceeds the maximum size of 8 bytes",
+      ),
+      (
+        ErrorKind::InvalidEscape { sequence: string("\\q") },
+        "invalid escape",
+        "invalid escape sequence `\\q`",
+      ),
+      (
+        ErrorKind::UnterminatedHereDoc { tag: string("END") },
+        "unterminated here-doc",
+        "unterminated here-doc, expected a line consisting of `END`",
+      ),
+      (
+        ErrorKind::InvalidCharacter { found: string("#\\foo") },
+        "invalid character",
+        "invalid character literal `#\\foo`",
+      ),
+      (ErrorKind::IntegerOverflow, "integer overflow", "integer literal out of range"),
+      (ErrorKind::TabInSource, "tab character", "encountered tab character"),
+      (
+        ErrorKind::UnsupportedReaderSyntax { form: string("#(") },
+        "unsupported reader syntax",
+        "reader syntax `#(` is not yet supported",
+      ),
+      (
+        ErrorKind::ReservedSymbolStart { ch: '#' },
+        "reserved symbol start",
+        "symbol cannot start with `#`, which is reserved for reader syntax",
+      ),
+      (
+        ErrorKind::EmptySymbol,
+        "empty symbol",
+        "expected reader syntax after `#`, found nothing",
+      ),
+      (
+        ErrorKind::UndefinedLabel { label: 1 },
+        "undefined label",
+        "reference to undefined datum label #1#",
+      ),
+      (
+        ErrorKind::UnexpectedEof { expected: "a datum" },
+        "unexpected end of input",
+        "unexpected end of input, expected a datum",
+      ),
+      (
+        ErrorKind::TooManyErrors { limit: 3 },
+        "too many errors",
+        "too many errors, stopping after 3",
+      ),
+      (
+        ErrorKind::TokenTooLong { limit: 5 },
+        "token too long",
+        "token exceeds the maximum length of 5 bytes",
+      ),
+      (
+        ErrorKind::ListTooLong { limit: 2 },
+        "list too long",
+        "list exceeds the maximum length of 2 elements",
+      ),
+      (
+        ErrorKind::UnquoteOutsideQuasiquote { found: Unquote },
+        "unquote outside quasiquote",
+        "`,` outside of a quasiquote",
+      ),
+      (
+        ErrorKind::AbbreviationInData { found: Quote },
+        "abbreviation in data",
+        "unexpected `'` abbreviation in data",
+      ),
+      (
+        ErrorKind::UnexpectedDot,
+        "unexpected dot",
+        "unexpected `.` outside of a dotted pair",
+      ),
+      (
+        ErrorKind::UnexpectedToken { found: RParen },
+        "unexpected token",
+        "unexpected `)`",
+      ),
+      (
+        ErrorKind::OddMapEntries,
+        "missing map value",
+        "expected a value for every key in map",
+      ),
+      (
+        ErrorKind::ReservedBracket { found: LBrace },
+        "reserved bracket",
+        "`{` is reserved for future syntax",
+      ),
+      (
+        ErrorKind::UnexpectedBracket {
+          expected: RBracket,
+          found: RParen,
+          opener: Span { start: 0, end: 1 },
+        },
+        "mismatched bracket",
+        "expected `]` to close preceding `[`, found `)` instead",
+      ),
+      (
+        ErrorKind::UnmatchedBracket { expected: RParen },
+        "unmatched bracket",
+        "expected `)` to close preceding `(`",
+      ),
+    ];
+
+    for (kind, summary, full) in cases {
+      let error = Error { span: Span { start: 0, end: 1 }, kind };
+      assert_eq!(error.summary(), summary);
+      assert_eq!(error.to_string(), full);
+      assert_ne!(summary, full);
+    }
+  }
+
+  #[test]
+  fn render_rustc() {
+    const INPUT: &str = "(define x 1)\n(foo [bar)\n";
+
+    let error = parse(INPUT).unwrap_err();
+    assert_eq!(
+      error,
+      Error {
+        span: Span { start: 22, end: 23 },
+        kind: ErrorKind::UnexpectedBracket {
+          expected: TokenKind::RBracket,
+          found: TokenKind::RParen,
+          opener: Span { start: 18, end: 19 },
+        },
+      },
+    );
+    assert_eq!(
+      error.render_rustc(INPUT, "test.scm"),
+      concat!(
+        "error: expected `]` to close preceding `[`, found `)` instead\n",
+        " --> test.scm:2:10\n",
+        "  |\n",
+        "2 | (foo [bar)\n",
+        "  |      -   ^\n",
+        "  |      |\n",
+        "  |      opened here\n",
+      ),
+    );
+
+    const MULTILINE: &str = "(foo [bar\n\n baz)\n";
+
+    let error = parse(MULTILINE).unwrap_err();
+    assert_eq!(
+      error.render_rustc(MULTILINE, "test.scm"),
+      concat!(
+        "error: expected `]` to close preceding `[`, found `)` instead\n",
+        " --> test.scm:3:5\n",
+        "  |\n",
+        "1 | (foo [bar\n",
+        "  |      - opened here\n",
+        "...\n",
+        "3 |  baz)\n",
+        "  |     ^\n",
+      ),
+    );
+  }
+
+  #[test]
+  fn display_syntax_errors() {
+    const INPUT: &str = "(a\n  @ b)\n)";
+
+    let errors = SyntaxErrors::new(
+      INPUT,
+      vec![
+        Error { span: Span { start: 5, end: 6 }, kind: ErrorKind::InvalidToken },
+        Error {
+          span: Span { start: 10, end: 11 },
+          kind: ErrorKind::UnexpectedToken { found: TokenKind::RParen },
+        },
+      ],
+    );
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+      errors.to_string(),
+      "2:3: encountered invalid token\n3:1: unexpected `)`",
+    );
+  }
+}
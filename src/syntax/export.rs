@@ -0,0 +1,202 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Serialisation of syntax trees for consumption by tools for other Lisp dialects.
+
+use std::fmt::Write;
+
+use crate::syntax::{
+  literal::{write_string, write_symbol},
+  parser::format_float,
+  BracketKind, SExpr, SymbolStyle,
+};
+
+/// The conventions with which to export a syntax tree.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ExportStyle {
+  /// The kind of bracket with which to delimit lists.
+  pub lists: BracketKind,
+  /// The kind of bracket with which to delimit lists written with square brackets,
+  /// which tools for Clojure-like dialects treat as vectors, or `None` to delimit them
+  /// like any other list.
+  pub vectors: Option<BracketKind>,
+  /// The kind of bracket with which to delimit maps.
+  pub maps: BracketKind,
+  /// The way in which to write symbols.
+  ///
+  /// Since EDN has no notation for symbols such as `|hello world|` that would not be
+  /// read back as the same symbol if written verbatim, they are written between pipes
+  /// by default, which at least keeps them intact.
+  pub symbols: SymbolStyle,
+}
+
+impl Default for ExportStyle {
+  /// Delimit every list by round brackets and every map by curly brackets, and write
+  /// symbols between pipes where necessary.
+  fn default() -> Self {
+    Self {
+      lists: BracketKind::Paren,
+      vectors: None,
+      maps: BracketKind::Curly,
+      symbols: SymbolStyle::Quoted,
+    }
+  }
+}
+
+/// Serialise a syntax tree into an EDN-like notation according to the given style.
+///
+/// Unlike the canonical Scheme notation, Booleans are written as `true` and `false`,
+/// non-finite floating-point numbers are written as `##Inf`, `##-Inf`, and `##NaN`,
+/// characters are written as `\c` or `\uXXXX`, and each kind of collection is delimited
+/// by the brackets given in the style. Top-level forms are separated by newlines.
+pub fn export(tree: &[SExpr], style: &ExportStyle) -> String {
+  let mut output = String::new();
+  for (i, sexpr) in tree.iter().enumerate() {
+    if i > 0 {
+      output.push('\n');
+    }
+    export_sexpr(&mut output, sexpr, style);
+  }
+
+  output
+}
+
+/// Serialise a single symbolic expression into the given output buffer.
+fn export_sexpr(output: &mut String, sexpr: &SExpr, style: &ExportStyle) {
+  match sexpr {
+    SExpr::Symbol(name) => match style.symbols {
+      SymbolStyle::Quoted => write_symbol(output, name).unwrap(),
+      SymbolStyle::Bare => output.push_str(name),
+    },
+    SExpr::Keyword(name) => write!(output, ":{name}").unwrap(),
+    SExpr::String(string) => write_string(output, string).unwrap(),
+    // NOTE: EDN has no notation for integers in other radices.
+    SExpr::Int(value, _) => write!(output, "{value}").unwrap(),
+    SExpr::Float(value) if value.is_nan() => output.push_str("##NaN"),
+    SExpr::Float(value) if value.is_infinite() => {
+      output.push_str(if *value > 0.0 { "##Inf" } else { "##-Inf" })
+    },
+    SExpr::Float(value) => output.push_str(&format_float(*value)),
+    SExpr::Bool(value) => write!(output, "{value}").unwrap(),
+    SExpr::Char(c) => match c {
+      '\n' => output.push_str("\\newline"),
+      ' ' => output.push_str("\\space"),
+      '\t' => output.push_str("\\tab"),
+      '\r' => output.push_str("\\return"),
+      c if c.is_control() || c.is_whitespace() => {
+        write!(output, "\\u{:04x}", *c as u32).unwrap()
+      },
+      c => write!(output, "\\{c}").unwrap(),
+    },
+    SExpr::List(list, bracket) => {
+      let bracket = match (bracket, style.vectors) {
+        (BracketKind::Square, Some(vectors)) => vectors,
+        _ => style.lists,
+      };
+      output.push(bracket.opener());
+      for (i, sexpr) in list.iter().enumerate() {
+        if i > 0 {
+          output.push(' ');
+        }
+        export_sexpr(output, sexpr, style);
+      }
+      output.push(bracket.closer());
+    },
+    SExpr::Map(entries) => {
+      output.push(style.maps.opener());
+      for (i, (key, value)) in entries.iter().enumerate() {
+        if i > 0 {
+          output.push(' ');
+        }
+        export_sexpr(output, key, style);
+        output.push(' ');
+        export_sexpr(output, value, style);
+      }
+      output.push(style.maps.closer());
+    },
+    // EDN has no notation for shared structure, so we fall back to that of Scheme.
+    SExpr::Labelled(label, datum) => {
+      write!(output, "#{label}=").unwrap();
+      export_sexpr(output, datum, style);
+    },
+    SExpr::LabelRef(label) => write!(output, "#{label}#").unwrap(),
+    // NOTE: An invalid token cannot be represented faithfully, so it is passed through.
+    SExpr::Invalid { text, .. } => output.push_str(text),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse, parse_with_options, Error, ParseOptions};
+
+  #[test]
+  fn export_brackets() -> Result<(), Error> {
+    let tree = parse("(foo [1 #t] \"bar\" 2.5) {}")?;
+
+    let style = ExportStyle::default();
+    assert_eq!(export(&tree, &style), "(foo (1 true) \"bar\" 2.5)\n()");
+
+    let style = ExportStyle { lists: BracketKind::Square, ..ExportStyle::default() };
+    assert_eq!(export(&tree, &style), "[foo [1 true] \"bar\" 2.5]\n[]");
+
+    Ok(())
+  }
+
+  #[test]
+  fn export_vectors() -> Result<(), Error> {
+    let tree = parse("(foo [1 #t] {})")?;
+
+    // Lists written with square brackets may be exported as vectors.
+    let style =
+      ExportStyle { vectors: Some(BracketKind::Square), ..ExportStyle::default() };
+    assert_eq!(export(&tree, &style), "(foo [1 true] ())");
+
+    let style = ExportStyle {
+      lists: BracketKind::Square,
+      vectors: Some(BracketKind::Curly),
+      ..ExportStyle::default()
+    };
+    assert_eq!(export(&tree, &style), "[foo {1 true} []]");
+
+    Ok(())
+  }
+
+  #[test]
+  fn export_maps() -> Result<(), Error> {
+    let options = ParseOptions { braces_as_maps: true, ..ParseOptions::default() };
+    let tree = parse_with_options("{:a [1] :b {}}", &options)?;
+
+    let style =
+      ExportStyle { vectors: Some(BracketKind::Square), ..ExportStyle::default() };
+    assert_eq!(export(&tree, &style), "{:a [1] :b {}}");
+
+    let style = ExportStyle { maps: BracketKind::Square, ..ExportStyle::default() };
+    assert_eq!(export(&tree, &style), "[:a (1) :b []]");
+
+    Ok(())
+  }
+
+  #[test]
+  fn export_symbols() -> Result<(), Error> {
+    let tree = parse("(|a b| c |d)e|)")?;
+    assert_eq!(export(&tree, &ExportStyle::default()), "(|a b| c |d)e|)");
+    assert_eq!(export(&[SExpr::from("a b")], &ExportStyle::default()), "|a b|");
+
+    let style = ExportStyle { symbols: SymbolStyle::Bare, ..ExportStyle::default() };
+    assert_eq!(export(&tree, &style), "(a b c d)e)");
+
+    Ok(())
+  }
+
+  #[test]
+  fn export_chars() -> Result<(), Error> {
+    let tree = parse(r"#\a #\space #\newline #\x7 #\λ")?;
+    assert_eq!(
+      export(&tree, &ExportStyle::default()),
+      "\\a\n\\space\n\\newline\n\\u0007\n\\λ"
+    );
+
+    Ok(())
+  }
+}
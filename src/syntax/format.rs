@@ -0,0 +1,407 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Canonical formatting of source code.
+
+use crate::syntax::{
+  parser::{strip_bom, strip_shebang},
+  Error, Lexer, LexerOptions, ParseOptions, Parser, SExpr, TokenKind,
+};
+
+/// The maximum width of a line of formatted source code, in characters.
+const WIDTH: usize = 80;
+
+/// The number of columns by which the body of a special form is indented.
+const BODY_INDENT: usize = 2;
+
+/// A strategy for indenting the operands of a list over several lines.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Indent {
+  /// Align every operand with the first operand.
+  Align,
+  /// Align the given number of distinguished operands with the first operand, and
+  /// indent the remaining operands, which form the body, relative to the list itself.
+  Body(usize),
+}
+
+/// The indentation strategies for special forms, by the symbol that heads them.
+///
+/// Any other list falls back to [`Indent::Align`].
+const INDENT_RULES: [(&str, Indent); 4] = [
+  ("cond", Indent::Align),
+  ("define", Indent::Body(1)),
+  ("lambda", Indent::Body(1)),
+  ("let", Indent::Body(1)),
+];
+
+/// Format source code in the canonical style.
+///
+/// Each top-level form is written on its own line, or over several lines if it does not
+/// fit within 80 columns, in which case the operands of a list are aligned with its
+/// first operand. The exception is special forms such as `define`, `lambda`, and
+/// `let`, whose bodies are instead indented by two columns. Abbreviations such as
+/// `'datum` are preserved.
+///
+/// Comments between top-level forms are preserved, each on its own line unless it
+/// follows a form on the same line, as are datum comments. Since there is nowhere to
+/// put comments within a form once it has been reformatted, a form that contains any
+/// is instead kept as written. Blank lines between top-level forms and comments are
+/// preserved so that related forms stay grouped, although a run of several blank
+/// lines is collapsed into one. A leading shebang line is kept too, but a byte order
+/// mark is not.
+pub fn format_source(input: &str) -> Result<String, Error> {
+  let output = format_program(input)?;
+  debug_assert_eq!(
+    format_program(&output).as_ref(),
+    Ok(&output),
+    "formatting should be idempotent",
+  );
+
+  Ok(output)
+}
+
+/// Check whether source code is already in the canonical style, such that formatting it
+/// would not change it.
+///
+/// Source code that cannot be parsed is never considered to be formatted.
+pub fn is_formatted(input: &str) -> bool {
+  format_source(input).is_ok_and(|output| output == input)
+}
+
+/// Format a program, with each top-level form followed by a newline.
+fn format_program(input: &str) -> Result<String, Error> {
+  let options = ParseOptions::default();
+  // NOTE: The spans of the forms are relative to the input after stripping.
+  let input = strip_bom(input);
+  let (shebang, input) = input.split_at(input.len() - strip_shebang(input).len());
+
+  let mut output = String::new();
+  let mut end = 0;
+  for result in Parser::new(input, &options).spanned() {
+    let (sexpr, span) = result?;
+    format_gap(&mut output, &input[end..span.start], true);
+    end = span.end;
+
+    let text = &input[span.start..span.end];
+    if contains_comments(text) {
+      output.push_str(text);
+    } else {
+      format_sexpr(&mut output, &sexpr, 0);
+    }
+  }
+  format_gap(&mut output, &input[end..], false);
+
+  match shebang.trim_end() {
+    "" => Ok(output),
+    shebang => Ok(format!("{shebang}\n{output}")),
+  }
+}
+
+/// Format the text before, between, or after top-level forms, which consists of
+/// comments and whitespace, into the output buffer.
+///
+/// Unless the output is empty, it is expected to end with the preceding form, and the
+/// text on the rest of its line is kept there. Each other line is written on its own,
+/// except that runs of blank lines are collapsed into one and dropped entirely at the
+/// start and end of the output. If a form follows, the output is left at the start of
+/// a line for it.
+fn format_gap(output: &mut String, gap: &str, followed: bool) {
+  let mut lines: Vec<_> = gap.split('\n').map(str::trim).collect();
+  if !output.is_empty() {
+    let rest = lines.remove(0);
+    if !rest.is_empty() {
+      output.push(' ');
+      output.push_str(rest);
+    }
+    output.push('\n');
+  }
+  // NOTE: The last line is the one on which the following form starts, so it is not
+  //       blank even if nothing precedes the form.
+  if followed && lines.last().is_some_and(|line| line.is_empty()) {
+    lines.pop();
+  }
+
+  let mut blank = false;
+  for line in lines {
+    if line.is_empty() {
+      blank = true;
+      continue;
+    }
+    if blank && !output.is_empty() {
+      output.push('\n');
+    }
+    blank = false;
+    output.push_str(line);
+    output.push('\n');
+  }
+  if blank && followed && !output.is_empty() {
+    output.push('\n');
+  }
+}
+
+/// Check whether the source code of a form contains any comments.
+fn contains_comments(text: &str) -> bool {
+  let options = LexerOptions { emit_comments: true, ..LexerOptions::default() };
+  Lexer::with_options(text, &options)
+    .any(|token| matches!(token.kind, TokenKind::Comment | TokenKind::DatumComment))
+}
+
+/// Format a symbolic expression that starts at the given column into the output buffer.
+fn format_sexpr(output: &mut String, sexpr: &SExpr, column: usize) {
+  let flat = flatten(sexpr);
+  if column + flat.chars().count() <= WIDTH {
+    output.push_str(&flat);
+    return;
+  }
+
+  if let Some((prefix, datum)) = abbreviation(sexpr) {
+    output.push_str(prefix);
+    format_sexpr(output, datum, column + prefix.len());
+    return;
+  }
+
+  match sexpr {
+    SExpr::List(list, bracket) if !list.is_empty() => {
+      output.push(bracket.opener());
+      match indent_rule(&list[0]) {
+        Indent::Align => format_aligned(output, list, column + 1),
+        Indent::Body(distinguished) => {
+          format_body(output, list, distinguished, column + 1)
+        },
+      }
+      output.push(bracket.closer());
+    },
+    SExpr::Map(entries) if !entries.is_empty() => {
+      output.push('{');
+      for (i, (key, value)) in entries.iter().enumerate() {
+        if i > 0 {
+          newline(output, column + 1);
+        }
+        let key = flatten(key);
+        output.push_str(&key);
+        output.push(' ');
+        format_sexpr(output, value, column + 1 + key.chars().count() + 1);
+      }
+      output.push('}');
+    },
+    SExpr::Labelled(label, datum) => {
+      let prefix = format!("#{label}=");
+      output.push_str(&prefix);
+      format_sexpr(output, datum, column + prefix.len());
+    },
+    _ => output.push_str(&flat),
+  }
+}
+
+/// Format the non-empty elements of a list, starting at the given column, by aligning
+/// its operands.
+fn format_aligned(output: &mut String, list: &[SExpr], column: usize) {
+  // NOTE: If the head of a list is an atom, the operands are aligned with the first
+  //       operand, and otherwise every element is aligned with the head.
+  let (column, rest) = match list {
+    [head, first, rest @ ..] if !is_compound(head) => {
+      let head = flatten(head);
+      output.push_str(&head);
+      output.push(' ');
+
+      let column = column + head.chars().count() + 1;
+      format_sexpr(output, first, column);
+      (column, rest)
+    },
+    [head, rest @ ..] => {
+      format_sexpr(output, head, column);
+      (column, rest)
+    },
+    [] => unreachable!(),
+  };
+  for sexpr in rest {
+    newline(output, column);
+    format_sexpr(output, sexpr, column);
+  }
+}
+
+/// Format the non-empty elements of a special form, starting at the given column, with
+/// the given number of distinguished operands before its body.
+fn format_body(
+  output: &mut String,
+  list: &[SExpr],
+  distinguished: usize,
+  column: usize,
+) {
+  let head = flatten(&list[0]);
+  output.push_str(&head);
+
+  let operands = &list[1..];
+  let (distinguished, body) = operands.split_at(distinguished.min(operands.len()));
+  let operand_column = column + head.chars().count() + 1;
+  for (i, sexpr) in distinguished.iter().enumerate() {
+    if i == 0 {
+      output.push(' ');
+    } else {
+      newline(output, operand_column);
+    }
+    format_sexpr(output, sexpr, operand_column);
+  }
+
+  // NOTE: The body is indented relative to the opening bracket, which precedes the
+  //       given column.
+  let body_column = column - 1 + BODY_INDENT;
+  for sexpr in body {
+    newline(output, body_column);
+    format_sexpr(output, sexpr, body_column);
+  }
+}
+
+/// Get the indentation strategy for a list with the given head.
+fn indent_rule(head: &SExpr) -> Indent {
+  head
+    .as_symbol()
+    .and_then(|name| INDENT_RULES.iter().find(|&&(form, _)| form == name))
+    .map_or(Indent::Align, |&(_, indent)| indent)
+}
+
+/// Format a symbolic expression on a single line.
+fn flatten(sexpr: &SExpr) -> String {
+  if let Some((prefix, datum)) = abbreviation(sexpr) {
+    return format!("{prefix}{}", flatten(datum));
+  }
+
+  match sexpr {
+    SExpr::List(list, bracket) => {
+      let elements: Vec<_> = list.iter().map(flatten).collect();
+      format!("{}{}{}", bracket.opener(), elements.join(" "), bracket.closer())
+    },
+    SExpr::Map(entries) => {
+      let entries: Vec<_> = entries
+        .iter()
+        .map(|(key, value)| format!("{} {}", flatten(key), flatten(value)))
+        .collect();
+      format!("{{{}}}", entries.join(" "))
+    },
+    SExpr::Labelled(label, datum) => format!("#{label}={}", flatten(datum)),
+    _ => sexpr.to_string(),
+  }
+}
+
+/// Get the prefix and datum of a symbolic expression if it is an abbreviation.
+fn abbreviation(sexpr: &SExpr) -> Option<(&'static str, &SExpr)> {
+  None
+    .or_else(|| sexpr.as_quoted().map(|datum| ("'", datum)))
+    .or_else(|| sexpr.as_quasiquoted().map(|datum| ("`", datum)))
+    .or_else(|| sexpr.as_unquoted().map(|datum| (",", datum)))
+    .or_else(|| sexpr.as_unquote_spliced().map(|datum| (",@", datum)))
+}
+
+/// Check whether a symbolic expression contains other symbolic expressions.
+fn is_compound(sexpr: &SExpr) -> bool {
+  matches!(sexpr, SExpr::List(..) | SExpr::Map(_) | SExpr::Labelled(..))
+}
+
+/// Start a new line that is indented to the given column.
+fn newline(output: &mut String, column: usize) {
+  output.push('\n');
+  output.push_str(&" ".repeat(column));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn format_flat() -> Result<(), Error> {
+    assert_eq!(
+      format_source("(define  x\n  'y)   [a   (b)]")?,
+      "(define x 'y)\n[a (b)]\n"
+    );
+    assert_eq!(format_source("")?, "");
+
+    Ok(())
+  }
+
+  #[test]
+  fn format_long_lists() -> Result<(), Error> {
+    let input = format!("(frob (f x) (g {}) ((h) 1 2))", "x ".repeat(30));
+    let expected =
+      format!("(frob (f x)\n      (g {})\n      ((h) 1 2))\n", "x ".repeat(29) + "x",);
+    assert_eq!(format_source(&input)?, expected);
+
+    let input = format!("((a) {})", "b ".repeat(40));
+    assert_eq!(format_source(&input)?, format!("((a){})\n", "\n b".repeat(40)));
+
+    Ok(())
+  }
+
+  #[test]
+  fn format_special_forms() -> Result<(), Error> {
+    let body = format!("(g {})", "x ".repeat(30).trim_end());
+
+    let input = format!("(let ((x 1) (y 2)) {body} (h x y))");
+    let expected = format!("(let ((x 1) (y 2))\n  {body}\n  (h x y))\n");
+    assert_eq!(format_source(&input)?, expected);
+
+    // Bindings that do not fit on one line should be aligned with each other.
+    let input = format!("(let ((x {body} {body}) (y 2)) y)");
+    let expected = format!("(let ((x {body}\n         {body})\n      (y 2))\n  y)\n");
+    assert_eq!(format_source(&input)?, expected);
+
+    let input = format!("(define (f x) (lambda (y) {body} {body}))");
+    let expected = format!("(define (f x)\n  (lambda (y)\n    {body}\n    {body}))\n");
+    assert_eq!(format_source(&input)?, expected);
+
+    let input = format!("(cond ((a) {body}) (else b))");
+    let expected = format!("(cond ((a) {body})\n      (else b))\n");
+    assert_eq!(format_source(&input)?, expected);
+
+    Ok(())
+  }
+
+  #[test]
+  fn preserve_blank_lines() -> Result<(), Error> {
+    assert_eq!(
+      format_source("(define x 1)\n\n\n(define y 2)")?,
+      "(define x 1)\n\n(define y 2)\n"
+    );
+    assert_eq!(format_source("\n\n(a)\n \n(b) (c)\n(d)\n\n")?, "(a)\n\n(b)\n(c)\n(d)\n");
+    // A line containing a comment is not blank.
+    assert_eq!(format_source("(a)\n; comment\n(b)")?, "(a)\n; comment\n(b)\n");
+    assert_eq!(format_source("(a)\n\n\n; comment\n\n(b)")?, "(a)\n\n; comment\n\n(b)\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn preserve_comments() -> Result<(), Error> {
+    assert_eq!(format_source("; only a comment")?, "; only a comment\n");
+    assert_eq!(
+      format_source(
+        "\n; header\n\n(define  x 1)   ; trailing\n  ; indented\n(f x)\n; end\n\n"
+      )?,
+      "; header\n\n(define x 1) ; trailing\n; indented\n(f x)\n; end\n"
+    );
+    assert_eq!(format_source("(a) #;(b  c) (d)")?, "(a) #;(b  c)\n(d)\n");
+
+    // A form containing a comment is kept as written.
+    let input = "(define (f x)\n    ; a comment\n    (g  x))\n";
+    assert_eq!(format_source(input)?, input);
+    assert_eq!(format_source("(a #;b  c)")?, "(a #;b  c)\n");
+
+    // So is a shebang line.
+    assert_eq!(
+      format_source("#!/usr/bin/env luna\n( a )")?,
+      "#!/usr/bin/env luna\n(a)\n"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn formatted() {
+    assert!(is_formatted("(define x 1)\n'(a b)\n"));
+    assert!(is_formatted("(define x 1)\n\n(define y 2)\n"));
+    assert!(is_formatted("; comment\n(define x 1) ; comment\n"));
+    assert!(!is_formatted("(define x 1)\n\n\n(define y 2)\n"));
+    assert!(!is_formatted("(define  x 1)"));
+    assert!(!is_formatted("(define x 1)\n\n"));
+    assert!(!is_formatted("(define x"));
+  }
+}
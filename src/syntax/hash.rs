@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Hashing of syntax trees.
+
+use crate::syntax::SExpr;
+
+/// Compute a hash of the semantic structure of a syntax tree.
+///
+/// As with [`SExpr::value_eq`], this ignores the kind of bracket that delimits each
+/// list and the radix in which each integer is written. Since whitespace and comments
+/// are not part of a syntax tree in the first place, two programs that differ only in
+/// their formatting hash identically.
+///
+/// The hash is computed with 64-bit FNV-1a over a platform-independent encoding of the
+/// tree, so it is stable across runs and machines and hence suitable as a cache key.
+/// Note that all NaNs hash identically.
+pub fn semantic_hash(tree: &[SExpr]) -> u64 {
+  let mut hasher = Fnv1a::new();
+  hasher.write_len(tree.len());
+  for sexpr in tree {
+    hash_sexpr(&mut hasher, sexpr);
+  }
+
+  hasher.0
+}
+
+/// Feed a symbolic expression into a hasher.
+fn hash_sexpr(hasher: &mut Fnv1a, sexpr: &SExpr) {
+  // NOTE: Each variant is prefixed by a distinct tag so that, for example, a symbol
+  //       and a string with the same contents hash differently.
+  match sexpr {
+    SExpr::Symbol(name) => {
+      hasher.write(&[0]);
+      hasher.write_str(name);
+    },
+    SExpr::Keyword(name) => {
+      hasher.write(&[1]);
+      hasher.write_str(name);
+    },
+    SExpr::String(string) => {
+      hasher.write(&[2]);
+      hasher.write_str(string);
+    },
+    SExpr::Int(value, _) => {
+      hasher.write(&[3]);
+      hasher.write(&value.to_le_bytes());
+    },
+    SExpr::Float(value) => {
+      hasher.write(&[4]);
+      let bits = if value.is_nan() { f64::NAN.to_bits() } else { value.to_bits() };
+      hasher.write(&bits.to_le_bytes());
+    },
+    SExpr::Bool(value) => hasher.write(&[5, u8::from(*value)]),
+    SExpr::Char(c) => {
+      hasher.write(&[6]);
+      hasher.write(&u32::from(*c).to_le_bytes());
+    },
+    SExpr::List(list, _) => {
+      hasher.write(&[7]);
+      hasher.write_len(list.len());
+      for sexpr in list {
+        hash_sexpr(hasher, sexpr);
+      }
+    },
+    SExpr::Map(entries) => {
+      hasher.write(&[8]);
+      hasher.write_len(entries.len());
+      for (key, value) in entries {
+        hash_sexpr(hasher, key);
+        hash_sexpr(hasher, value);
+      }
+    },
+    SExpr::Labelled(label, datum) => {
+      hasher.write(&[9]);
+      hasher.write(&label.to_le_bytes());
+      hash_sexpr(hasher, datum);
+    },
+    SExpr::LabelRef(label) => {
+      hasher.write(&[10]);
+      hasher.write(&label.to_le_bytes());
+    },
+    // NOTE: The span is ignored, since it only reflects the formatting of the input.
+    SExpr::Invalid { text, .. } => {
+      hasher.write(&[11]);
+      hasher.write_str(text);
+    },
+  }
+}
+
+/// The state of a 64-bit FNV-1a hash.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+  /// The initial state of the hash.
+  const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+  /// The multiplier applied after each byte.
+  const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+  /// Create a new hash in its initial state.
+  fn new() -> Self {
+    Self(Self::OFFSET_BASIS)
+  }
+
+  /// Feed some bytes into the hash.
+  fn write(&mut self, bytes: &[u8]) {
+    for &byte in bytes {
+      self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+    }
+  }
+
+  /// Feed a length into the hash with the same encoding on every platform.
+  fn write_len(&mut self, len: usize) {
+    self.write(&(len as u64).to_le_bytes());
+  }
+
+  /// Feed a length-prefixed string into the hash.
+  fn write_str(&mut self, string: &str) {
+    self.write_len(string.len());
+    self.write(string.as_bytes());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse, Error};
+
+  #[test]
+  fn ignore_formatting() -> Result<(), Error> {
+    let hash = |input| parse(input).map(|tree| semantic_hash(&tree));
+
+    assert_eq!(hash("(a b)")?, hash("( a  b )")?);
+    assert_eq!(hash("(f [x] #xff) ; hi")?, hash("(f\n  (x)\n  255)")?);
+    assert_eq!(hash("{:a 1}")?, hash("{ :a  #b1 }")?);
+
+    assert_ne!(hash("(a b)")?, hash("(a b c)")?);
+    assert_ne!(hash("(a b)")?, hash("(a) b")?);
+    assert_ne!(hash("a")?, hash("\"a\"")?);
+    assert_ne!(hash("ab c")?, hash("a bc")?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn stable_hash() -> Result<(), Error> {
+    // The hash must not change between runs or platforms.
+    assert_eq!(semantic_hash(&[]), 0xa8c7_f832_281a_39c5);
+    assert_eq!(semantic_hash(&parse("(a \"b\" 1 [c] #0=d)")?), 0x82e2_7060_e7bf_276f);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,121 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Interning of symbol names.
+
+use std::collections::HashMap;
+
+/// A handle to a symbol name within an [`Interner`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SymbolId(usize);
+
+/// A table that maps each distinct symbol name to a [`SymbolId`].
+///
+/// By default, names are compared case-sensitively, as in R7RS. An interner created
+/// with [`Interner::ignoring_case`] instead treats names that differ only in case as
+/// the same symbol, for interoperation with case-insensitive sources. Either way, the
+/// original case of each name is preserved: [`Interner::resolve`] returns the spelling
+/// with which a symbol was first interned, and the [`SExpr`](crate::syntax::SExpr)
+/// being interned is never modified.
+#[derive(Clone, Default, Debug)]
+pub struct Interner {
+  /// The name of each symbol, indexed by its ID.
+  names: Vec<String>,
+  /// The ID of each symbol, keyed by its name, case-folded if case is ignored.
+  ids: HashMap<String, SymbolId>,
+  /// Whether names that differ only in case are the same symbol.
+  ignore_case: bool,
+}
+
+impl Interner {
+  /// Create a new, empty interner that compares names case-sensitively.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Create a new, empty interner that compares names case-insensitively.
+  pub fn ignoring_case() -> Self {
+    Self { ignore_case: true, ..Self::default() }
+  }
+
+  /// Check whether this interner compares names case-insensitively.
+  pub fn ignores_case(&self) -> bool {
+    self.ignore_case
+  }
+
+  /// Get the ID of a name, interning it first if it has not been seen before.
+  pub fn intern(&mut self, name: &str) -> SymbolId {
+    if let Some(id) = self.get(name) {
+      return id;
+    }
+
+    let id = SymbolId(self.names.len());
+    self.names.push(name.to_string());
+    self.ids.insert(self.key(name), id);
+    id
+  }
+
+  /// Get the ID of a name if it has been interned.
+  pub fn get(&self, name: &str) -> Option<SymbolId> {
+    self.ids.get(&self.key(name)).copied()
+  }
+
+  /// Get the name of a symbol as it was spelled when first interned.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the ID was not created by this interner.
+  pub fn resolve(&self, id: SymbolId) -> &str {
+    &self.names[id.0]
+  }
+
+  /// Get the number of distinct symbols that have been interned.
+  pub fn len(&self) -> usize {
+    self.names.len()
+  }
+
+  /// Check whether no symbols have been interned.
+  pub fn is_empty(&self) -> bool {
+    self.names.is_empty()
+  }
+
+  /// Get the key under which a name is looked up.
+  fn key(&self, name: &str) -> String {
+    if self.ignore_case {
+      fold_case(name).collect()
+    } else {
+      name.to_string()
+    }
+  }
+}
+
+/// Fold the case of a name for case-insensitive comparison.
+pub(crate) fn fold_case(name: &str) -> impl Iterator<Item = char> + '_ {
+  name.chars().flat_map(char::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn intern_names() {
+    let mut interner = Interner::new();
+    let foo = interner.intern("foo");
+    assert_eq!(interner.intern("foo"), foo);
+    assert_ne!(interner.intern("Foo"), foo);
+    assert_eq!(interner.get("FOO"), None);
+    assert_eq!(interner.len(), 2);
+  }
+
+  #[test]
+  fn intern_ignoring_case() {
+    let mut interner = Interner::ignoring_case();
+    let foo = interner.intern("Foo");
+    assert_eq!(interner.intern("foo"), foo);
+    assert_eq!(interner.get("FOO"), Some(foo));
+    assert_eq!(interner.len(), 1);
+    // The first spelling is preserved.
+    assert_eq!(interner.resolve(foo), "Foo");
+  }
+}
@@ -3,14 +3,18 @@
 
 //! Lexical analyser for Luna source code.
 
+use std::fmt;
+
 use derive_more::Display;
-use logos::Logos;
+use logos::{Filter, FilterResult, Logos};
 
-use crate::syntax::Span;
+use crate::syntax::{BracketKind, BytePos, Span};
 
 /// A token produced by a [`Lexer`].
-#[derive(Copy, Clone, Eq, PartialEq, Display, Debug)]
-#[display(fmt = "{kind}")]
+///
+/// A token is displayed as its kind by default, or along with its lexeme and span in
+/// the form ``symbol "foo" @3..6`` with the alternate flag, as in `{:#}`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Token<'a> {
   /// The lexical category of this token.
   pub kind: TokenKind,
@@ -20,8 +24,60 @@ pub struct Token<'a> {
   pub span: Span,
 }
 
+impl fmt::Display for Token<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if f.alternate() {
+      write!(f, "{} {:?} @{}", self.kind, self.lexeme, self.span)
+    } else {
+      write!(f, "{}", self.kind)
+    }
+  }
+}
+
+/// Options that control the behaviour of a [`Lexer`].
+#[derive(Copy, Clone, Default, Debug)]
+pub struct LexerOptions {
+  /// Whether to emit a [`TokenKind::Tab`] token for each tab character instead of
+  /// treating it as whitespace.
+  pub forbid_tabs: bool,
+  /// Whether to treat commas as whitespace, as in EDN.
+  ///
+  /// Since this conflicts with the unquote abbreviations, enabling it means that `,`
+  /// is skipped rather than lexed as a [`TokenKind::Unquote`] token, and that `,@` is
+  /// lexed as an [`TokenKind::Invalid`] token.
+  pub commas_as_whitespace: bool,
+  /// A predicate that determines which characters may appear within a symbol, or
+  /// `None` to use the default set of characters required by R7RS.
+  ///
+  /// When a predicate is given, the default rules are first used to find where a
+  /// symbol begins, and the symbol is then scanned manually as the longest run of
+  /// characters that satisfy the predicate. If the first character does not satisfy
+  /// the predicate, it is lexed as a [`TokenKind::Invalid`] token instead.
+  pub symbol_char: Option<fn(char) -> bool>,
+  /// Whether to emit a [`TokenKind::Comment`] token for each line comment instead of
+  /// skipping it.
+  pub emit_comments: bool,
+  /// Whether to permit underscores as digit separators within decimal integer and
+  /// floating-point literals, as in `1_000_000`.
+  ///
+  /// Each underscore must lie between two digits, so a number with a leading,
+  /// trailing, or doubled underscore, such as `_1`, `1_`, or `1__0`, is lexed as a
+  /// [`TokenKind::Invalid`] token. The lexemes of numbers retain their underscores.
+  pub allow_digit_separators: bool,
+  /// Whether to lex here-doc strings of the form `#<<TAG`, followed by a line ending,
+  /// the lines of the string, and a line consisting of exactly `TAG`.
+  ///
+  /// The tag is the rest of the line after `#<<` and must not be empty. A here-doc
+  /// without a terminating line extends to the end of the input.
+  pub allow_heredocs: bool,
+  /// Whether to emit a single [`TokenKind::Eof`] token with an empty span at the end
+  /// of the input, after any comments, before the lexer is exhausted.
+  pub emit_eof: bool,
+}
+
 /// The lexical category of a [`Token`].
 #[derive(Logos, Copy, Clone, Eq, PartialEq, Display, Debug)]
+#[logos(extras = LexerOptions)]
 pub enum TokenKind {
   /// A left bracket `(` character.
   #[display(fmt = "`(`")]
@@ -48,12 +104,52 @@ pub enum TokenKind {
   #[token("}")]
   RBrace,
 
+  /// A quote `'` character.
+  #[display(fmt = "`'`")]
+  #[token("'")]
+  Quote,
+  /// A quasiquote `` ` `` character.
+  #[display(fmt = "`` ` ``")]
+  #[token("`")]
+  Quasiquote,
+  /// An unquote `,` character.
+  #[display(fmt = "`,`")]
+  #[token(",", |lex| if lex.extras.commas_as_whitespace { Filter::Skip } else { Filter::Emit(()) })]
+  Unquote,
+  /// An unquote-splicing `,@` sequence.
+  #[display(fmt = "`,@`")]
+  #[token(",@", |lex| {
+    if lex.extras.commas_as_whitespace { FilterResult::Error } else { FilterResult::Emit(()) }
+  })]
+  UnquoteSplicing,
+
+  // NOTE: A lone dot would otherwise be lexed as a symbol, but it is reserved for
+  //       dotted pairs.
+  /// A dot `.` character.
+  #[display(fmt = "`.`")]
+  #[token(".")]
+  Dot,
+
   // The set of extended identifier characters conforms to the minimum set required by
   // the R7RS (Small) specification.
   /// A symbol (an interned kind of string).
   #[display(fmt = "symbol")]
   #[regex(r"(\p{XID_Continue}|!|\$|%|\*|\+|-|\.|/|:|<|=|>|\?|@|\^|_|~)+")]
   Symbol,
+  /// A symbol written between pipes, such as `|hello world|`, whose name may contain
+  /// any character and escape sequences as in a string literal.
+  #[display(fmt = "pipe-delimited symbol")]
+  #[token("|", pipe_symbol)]
+  PipeSymbol,
+  // NOTE: Keyword has a higher priority in order to avoid ambiguity with Symbol, as
+  //       a colon may also appear within a symbol.
+  /// A keyword (a self-evaluating symbol written with a leading colon).
+  #[display(fmt = "keyword")]
+  #[regex(
+    r":(\p{XID_Continue}|!|\$|%|\*|\+|-|\.|/|:|<|=|>|\?|@|\^|_|~)+",
+    priority = 3
+  )]
+  Keyword,
   /// A string literal.
   #[display(fmt = "string literal")]
   #[regex(r#""([^"\\]|\\.)*""#)]
@@ -62,20 +158,197 @@ pub enum TokenKind {
   /// An integer literal.
   #[display(fmt = "integer literal")]
   #[regex(r"(\+|-)?[0-9]+", priority = 2)]
+  #[regex(
+    r"#[bB](\+|-)?[01]+|#[oO](\+|-)?[0-7]+|#[dD](\+|-)?[0-9]+|#[xX](\+|-)?[0-9a-fA-F]+",
+    priority = 2
+  )]
   Int,
+  // NOTE: Float has a higher priority in order to avoid ambiguity with Symbol. A float
+  //       literal must contain either a decimal point or an exponent so that it is
+  //       not ambiguous with Int, or otherwise be one of the special literals for
+  //       infinity and NaN.
+  /// A floating-point literal.
+  #[display(fmt = "floating-point literal")]
+  #[regex(
+    r"(\+|-)?([0-9]+\.[0-9]*([eE](\+|-)?[0-9]+)?|\.[0-9]+([eE](\+|-)?[0-9]+)?|[0-9]+[eE](\+|-)?[0-9]+)",
+    priority = 2
+  )]
+  #[regex(r"(\+|-)(inf|nan)\.0", priority = 3)]
+  Float,
+  /// A here-doc string literal, which is only emitted if
+  /// [`LexerOptions::allow_heredocs`] is set.
+  #[display(fmt = "here-doc string")]
+  HereDoc,
   /// A Boolean literal.
   #[display(fmt = "Boolean literal")]
   #[regex(r"#t|#f|#true|#false")]
   Bool,
+  // NOTE: A delimiter may itself be written as a character literal, as in `#\(`, but
+  //       always ends the literal immediately.
+  /// A character literal, such as `#\a`, `#\space`, or `#\x41`.
+  #[display(fmt = "character literal")]
+  #[regex(
+    r#"#\\([^\p{Pattern_White_Space}()\[\]{}";]+|[\p{Pattern_White_Space}()\[\]{}";])"#
+  )]
+  Char,
+  /// A datum label definition of the form `#n=`.
+  #[display(fmt = "datum label")]
+  #[regex(r"#[0-9]+=")]
+  LabelDef,
+  /// A datum label reference of the form `#n#`.
+  #[display(fmt = "datum label reference")]
+  #[regex(r"#[0-9]+#")]
+  LabelRef,
+
+  /// A datum comment `#;` sequence, which comments out the datum that follows it.
+  #[display(fmt = "datum comment")]
+  #[token("#;")]
+  DatumComment,
+
+  // NOTE: This is a catch-all for the `#`-prefixed reader syntax that is reserved by
+  //       R7RS but not yet implemented, such as vectors and bytevectors. Cases
+  //       should be removed from here as they are implemented.
+  /// A `#`-prefixed reader syntax form that is not yet supported.
+  #[display(fmt = "unsupported reader syntax")]
+  #[regex(r#"#[^\p{Pattern_White_Space}()\[\]{}";\\#=]*\(?"#, priority = 0)]
+  UnsupportedReaderSyntax,
+
+  /// The end of the input, which is only emitted if [`LexerOptions::emit_eof`] is set.
+  #[display(fmt = "end of input")]
+  Eof,
+
+  /// A line comment, excluding its line ending, which is only emitted if
+  /// [`LexerOptions::emit_comments`] is set.
+  #[display(fmt = "comment")]
+  #[regex(r";[^\r\n]*", |lex| if lex.extras.emit_comments { Filter::Emit(()) } else { Filter::Skip })]
+  Comment,
+
+  /// A tab character, which is only emitted if [`LexerOptions::forbid_tabs`] is set.
+  #[display(fmt = "tab character")]
+  #[token("\t", |lex| if lex.extras.forbid_tabs { Filter::Emit(()) } else { Filter::Skip })]
+  Tab,
 
   /// A 'token' used for indicating errors encountered during lexical analysis.
-  #[regex(r"\p{Pattern_White_Space}+", logos::skip)] // Throw away whitespace...
-  #[regex(r"|;[^\r\n]*(\r\n|\n)?", logos::skip)] // ...and line comments.
+  // NOTE: Tabs are excluded here since they are handled by the Tab rule above.
+  // NOTE: Line comments are handled by the Comment rule above.
+  #[regex(r"[\p{Pattern_White_Space}--\t]+", logos::skip)] // Throw away whitespace.
   #[error]
   Invalid,
 }
 
 impl TokenKind {
+  /// Get every kind of token, in order of declaration.
+  ///
+  /// This is intended for tooling that builds syntax tables automatically, such as
+  /// documentation generators and exporters of editor grammars.
+  pub fn all() -> &'static [TokenKind] {
+    use TokenKind::*;
+
+    &[
+      LParen,
+      RParen,
+      LBracket,
+      RBracket,
+      LBrace,
+      RBrace,
+      Quote,
+      Quasiquote,
+      Unquote,
+      UnquoteSplicing,
+      Dot,
+      Symbol,
+      PipeSymbol,
+      Keyword,
+      String,
+      Int,
+      Float,
+      HereDoc,
+      Bool,
+      Char,
+      LabelDef,
+      LabelRef,
+      DatumComment,
+      UnsupportedReaderSyntax,
+      Eof,
+      Comment,
+      Tab,
+      Invalid,
+    ]
+  }
+
+  /// Describe the text that this kind of token matches, for human consumption.
+  pub fn pattern_description(&self) -> &'static str {
+    use TokenKind::*;
+
+    match self {
+      LParen => "`(`",
+      RParen => "`)`",
+      LBracket => "`[`",
+      RBracket => "`]`",
+      LBrace => "`{`",
+      RBrace => "`}`",
+      Quote => "`'`",
+      Quasiquote => "`` ` ``",
+      Unquote => "`,`",
+      UnquoteSplicing => "`,@`",
+      Dot => "`.` on its own",
+      Symbol => "one or more identifier characters, such as `foo` or `list->vector`",
+      PipeSymbol => "any characters or escape sequences between pipes, such as `|a b|`",
+      Keyword => "a colon followed by identifier characters, such as `:foo`",
+      String => "any characters or escape sequences between double quotes",
+      Int => {
+        "decimal digits with an optional sign, or `#b`, `#o`, `#d` or `#x` followed \
+         by digits in that radix"
+      },
+      Float => {
+        "a decimal number with a fractional part or an exponent, such as `1.5` \
+         or `1e3`, or one of `+inf.0`, `-inf.0`, `+nan.0` and `-nan.0`"
+      },
+      HereDoc => {
+        "`#<<` followed by a tag, a line ending, and then lines up to one \
+         consisting of the tag alone"
+      },
+      Bool => "one of `#t`, `#f`, `#true` and `#false`",
+      Char => {
+        "`#\\` followed by a character, a character name or a hexadecimal scalar \
+         value, such as `#\\a`, `#\\space` or `#\\x41`"
+      },
+      LabelDef => "`#` followed by decimal digits and `=`, such as `#0=`",
+      LabelRef => "`#` followed by decimal digits and `#`, such as `#0#`",
+      DatumComment => "`#;`",
+      UnsupportedReaderSyntax => "any other `#`-prefixed sequence, such as `#(`",
+      Eof => "the end of the input",
+      Comment => "`;` followed by any characters up to the end of the line",
+      Tab => "a tab character",
+      Invalid => "any character that does not begin another kind of token",
+    }
+  }
+
+  /// Check whether this token is an atom, which forms a complete datum on its own.
+  pub fn is_atom(&self) -> bool {
+    use TokenKind::*;
+
+    matches!(
+      self,
+      Symbol | PipeSymbol | Keyword | String | Int | Float | HereDoc | Bool | Char
+    )
+  }
+
+  /// Check whether this token is an opening bracket.
+  pub fn is_opener(&self) -> bool {
+    matches!(self, TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace)
+  }
+
+  /// Check whether this token is a closing bracket.
+  pub fn is_closer(&self) -> bool {
+    matches!(self, TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace)
+  }
+
+  /// Check whether this token is a bracket, whether opening or closing.
+  pub fn is_delimiter(&self) -> bool {
+    self.is_opener() || self.is_closer()
+  }
+
   /// Get the opening token for this token if it has one.
   pub fn opener(&self) -> TokenKind {
     use TokenKind::*;
@@ -99,6 +372,18 @@ impl TokenKind {
       _ => panic!("expected an opening delimiter token"),
     }
   }
+
+  /// Get the kind of bracket for this token if it is a bracket.
+  pub fn bracket_kind(&self) -> BracketKind {
+    use TokenKind::*;
+
+    match self {
+      LParen | RParen => BracketKind::Paren,
+      LBracket | RBracket => BracketKind::Square,
+      LBrace | RBrace => BracketKind::Curly,
+      _ => panic!("expected a delimiter token"),
+    }
+  }
 }
 
 /// The lexical analyser for Luna source code.
@@ -108,12 +393,99 @@ impl TokenKind {
 pub struct Lexer<'a> {
   /// The wrapped [`logos`] lexer struct.
   inner: logos::Lexer<'a, TokenKind>,
+  /// Whether a [`TokenKind::Eof`] token is yet to be emitted at the end of the input.
+  pending_eof: bool,
 }
 
 impl<'a> Lexer<'a> {
   /// Create a new lexer over a given input string.
   pub fn new(input: &'a str) -> Self {
-    Self { inner: TokenKind::lexer(input) }
+    Self::with_options(input, &LexerOptions::default())
+  }
+
+  /// Create a new lexer over a given input string with the given options.
+  pub fn with_options(input: &'a str, options: &LexerOptions) -> Self {
+    let pending_eof = options.emit_eof;
+    Self { inner: TokenKind::lexer_with_extras(input, *options), pending_eof }
+  }
+
+  /// Make this lexer emit a single [`TokenKind::Eof`] token with an empty span at the
+  /// end of the input before it is exhausted, as if [`LexerOptions::emit_eof`] were
+  /// set.
+  pub fn with_eof(mut self) -> Self {
+    self.pending_eof = true;
+    self
+  }
+
+  /// Get the slice of the input that has not yet been tokenised.
+  pub fn remaining(&self) -> &'a str {
+    self.inner.remainder()
+  }
+
+  /// Turn this lexer into an iterator over its tokens, each paired with the trivia that
+  /// precedes it.
+  ///
+  /// If some tokens have already been consumed, the trivia of the next token starts
+  /// after the last of them.
+  pub fn with_trivia(self) -> TriviaLexer<'a> {
+    let end = self.inner.span().end;
+    TriviaLexer { lexer: self, end }
+  }
+
+  /// Manually scan a symbol that starts at a given position according to a custom
+  /// predicate for symbol characters.
+  fn scan_symbol(
+    &mut self,
+    start: BytePos,
+    is_symbol_char: fn(char) -> bool,
+  ) -> (TokenKind, Span) {
+    let source = self.inner.source();
+    let rest = &source[start..];
+
+    let len: usize =
+      rest.chars().take_while(|&c| is_symbol_char(c)).map(char::len_utf8).sum();
+    let (kind, end) = if len > 0 {
+      (TokenKind::Symbol, start + len)
+    } else {
+      (TokenKind::Invalid, start + rest.chars().next().map_or(0, char::len_utf8))
+    };
+    self.restart_at(end);
+
+    (kind, Span { start, end })
+  }
+
+  /// Manually scan a here-doc string that starts with the `#<<` at a given position.
+  fn scan_heredoc(&mut self, start: BytePos) -> (TokenKind, Span) {
+    let source = self.inner.source();
+    let mut lines = source[start..].split_inclusive('\n');
+    let first = lines.next().unwrap_or_default();
+    let tag = first["#<<".len()..].trim_end_matches(['\r', '\n']);
+
+    let (kind, end) = if tag.is_empty() {
+      (TokenKind::Invalid, start + "#<<".len())
+    } else {
+      let mut end = start + first.len();
+      for line in lines {
+        if line.trim_end_matches(['\r', '\n']) == tag {
+          end += tag.len();
+          break;
+        }
+        end += line.len();
+      }
+      (TokenKind::HereDoc, end)
+    };
+    self.restart_at(end);
+
+    (kind, Span { start, end })
+  }
+
+  /// Restart the inner lexer at a given position.
+  fn restart_at(&mut self, pos: BytePos) {
+    // NOTE: The inner lexer may have lexed either more or less than a token that was
+    //       scanned manually, so we restart it from the end of that token.
+    let source = self.inner.source();
+    self.inner = TokenKind::lexer_with_extras(source, self.inner.extras);
+    self.inner.bump(pos);
   }
 }
 
@@ -121,16 +493,155 @@ impl<'a> Iterator for Lexer<'a> {
   type Item = Token<'a>;
 
   fn next(&mut self) -> Option<Self::Item> {
-    let kind = self.inner.next()?;
-    let lexeme = self.inner.slice();
+    let Some(kind) = self.inner.next() else {
+      if !self.pending_eof {
+        return None;
+      }
+      self.pending_eof = false;
+
+      let end = self.inner.source().len();
+      let span = Span { start: end, end };
+      return Some(Self::Item { kind: TokenKind::Eof, lexeme: "", span });
+    };
     let span = self.inner.span();
     // Convert from an std::ops::Range to a crate::syntax::Span.
     let span = Span { start: span.start, end: span.end };
 
+    let extras = self.inner.extras;
+    let (kind, span) = match (kind, extras.symbol_char) {
+      (TokenKind::UnsupportedReaderSyntax, _)
+        if extras.allow_heredocs && self.inner.slice().starts_with("#<<") =>
+      {
+        self.scan_heredoc(span.start)
+      },
+      (TokenKind::Symbol | TokenKind::Invalid, Some(is_symbol_char)) => {
+        self.scan_symbol(span.start, is_symbol_char)
+      },
+      _ => (kind, span),
+    };
+    let lexeme = &self.inner.source()[span.start..span.end];
+
+    // NOTE: A number containing underscores is lexed as a symbol by the rules above, so
+    //       it is reclassified here.
+    let kind = if kind == TokenKind::Symbol && self.inner.extras.allow_digit_separators {
+      separated_number(lexeme).unwrap_or(kind)
+    } else {
+      kind
+    };
+
     Some(Self::Item { kind, lexeme, span })
   }
 }
 
+/// Get the token that covers a byte position within an input string, such as the token
+/// beneath the cursor of an editor.
+///
+/// A token covers the positions from its start up to, but excluding, its end, so a
+/// position that lies within whitespace or a comment, or past the end of the input, is
+/// covered by no token and yields `None`. Only the input up to the end of the covering
+/// token is lexed.
+pub fn token_at(input: &str, pos: BytePos) -> Option<Token<'_>> {
+  // NOTE: Since tokens such as strings may contain arbitrary text, we cannot start
+  //       lexing from anywhere but the start of the input.
+  Lexer::new(input)
+    .find(|token| token.span.end > pos)
+    .filter(|token| token.span.start <= pos)
+}
+
+/// A token along with the trivia that precedes it, as produced by a [`TriviaLexer`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TokenWithLeadingTrivia<'a> {
+  /// The token itself.
+  pub token: Token<'a>,
+  /// The text between the end of the previous token, or the start of the input, and
+  /// the start of this token.
+  ///
+  /// This consists of whitespace, along with any comments that are not emitted as
+  /// tokens.
+  pub leading_ws: &'a str,
+}
+
+/// An iterator over the tokens of a [`Lexer`], each paired with the trivia that
+/// precedes it, as returned by [`Lexer::with_trivia`].
+///
+/// This is intended for linting the layout of source code, such as checking the
+/// indentation of each line. Trivia at the end of the input is only yielded if the
+/// lexer emits a [`TokenKind::Eof`] token, in which case it precedes that token.
+pub struct TriviaLexer<'a> {
+  /// The underlying lexer.
+  lexer: Lexer<'a>,
+  /// The position after the last token that was yielded.
+  end: BytePos,
+}
+
+impl<'a> Iterator for TriviaLexer<'a> {
+  type Item = TokenWithLeadingTrivia<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let token = self.lexer.next()?;
+    let leading_ws = &self.lexer.inner.source()[self.end..token.span.start];
+    self.end = token.span.end;
+
+    Some(TokenWithLeadingTrivia { token, leading_ws })
+  }
+}
+
+/// Lex the rest of a symbol written between pipes after its opening pipe, returning
+/// whether the closing pipe was found.
+///
+/// This is done manually so that an unterminated symbol is lexed as a lone invalid
+/// `|` rather than consuming the rest of the input.
+fn pipe_symbol(lex: &mut logos::Lexer<TokenKind>) -> bool {
+  let mut chars = lex.remainder().char_indices();
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '|' => {
+        lex.bump(i + 1);
+        return true;
+      },
+      // NOTE: An escaped character, such as `\|`, never closes the symbol.
+      '\\' => {
+        chars.next();
+      },
+      _ => {},
+    }
+  }
+
+  false
+}
+
+/// Get the kind of number that a lexeme containing digit separators represents, or
+/// `None` if it does not represent a number.
+///
+/// Numbers with misplaced separators are classified as [`TokenKind::Invalid`].
+fn separated_number(lexeme: &str) -> Option<TokenKind> {
+  if !lexeme.contains('_') {
+    return None;
+  }
+
+  let digits: String = lexeme.chars().filter(|&c| c != '_').collect();
+  let unsigned = digits.strip_prefix(['+', '-']).unwrap_or(&digits);
+  let kind = if !unsigned.is_empty() && unsigned.bytes().all(|b| b.is_ascii_digit()) {
+    TokenKind::Int
+  } else if unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+    && unsigned.bytes().all(|b| b.is_ascii_digit() || b".eE+-".contains(&b))
+    && unsigned.parse::<f64>().is_ok()
+  {
+    TokenKind::Float
+  } else {
+    return None;
+  };
+
+  let bytes = lexeme.as_bytes();
+  let is_digit =
+    |i: Option<usize>| i.and_then(|i| bytes.get(i)).is_some_and(u8::is_ascii_digit);
+  let well_placed = (0..bytes.len())
+    .filter(|&i| bytes[i] == b'_')
+    .all(|i| is_digit(i.checked_sub(1)) && is_digit(Some(i + 1)));
+
+  Some(if well_placed { kind } else { TokenKind::Invalid })
+}
+
 #[cfg(test)]
 mod tests {
   use TokenKind::*;
@@ -144,6 +655,22 @@ mod tests {
     assert_eq!(lexer.slice(), input);
   }
 
+  fn check_spanned(input: &str, expected: &[(TokenKind, Span)]) {
+    let tokens: Vec<_> =
+      Lexer::new(input).map(|token| (token.kind, token.span)).collect();
+    assert_eq!(tokens, expected);
+
+    // Each span should also cover exactly the lexeme of its token.
+    for token in Lexer::new(input) {
+      assert_eq!(&input[token.span.start..token.span.end], token.lexeme);
+    }
+  }
+
+  /// Construct a span from its start and end positions.
+  fn span(start: BytePos, end: BytePos) -> Span {
+    Span { start, end }
+  }
+
   #[test]
   fn lex_parens() {
     check("(", LParen);
@@ -162,6 +689,14 @@ mod tests {
     check("}", RBrace);
   }
 
+  #[test]
+  fn lex_abbreviations() {
+    check("'", Quote);
+    check("`", Quasiquote);
+    check(",", Unquote);
+    check(",@", UnquoteSplicing);
+  }
+
   #[test]
   fn lex_symbol() {
     check("foo", Symbol);
@@ -174,6 +709,66 @@ mod tests {
     check("long-function-name", Symbol);
     check("eq?", Symbol);
     check("set!", Symbol);
+    check("...", Symbol);
+    check(".foo", Symbol);
+  }
+
+  #[test]
+  fn lex_pipe_symbol() {
+    check("||", PipeSymbol);
+    check("|hello world|", PipeSymbol);
+    check("|a\\|b|", PipeSymbol);
+    check("|a\nb|", PipeSymbol);
+
+    // An unterminated symbol should not consume the rest of the input.
+    let kinds: Vec<_> = Lexer::new("(a | b)").map(|token| token.kind).collect();
+    assert_eq!(kinds, [LParen, Symbol, Invalid, Symbol, RParen]);
+  }
+
+  #[test]
+  fn lex_dot() {
+    check(".", Dot);
+    check_spanned(
+      "(a . b)",
+      &[
+        (LParen, span(0, 1)),
+        (Symbol, span(1, 2)),
+        (Dot, span(3, 4)),
+        (Symbol, span(5, 6)),
+        (RParen, span(6, 7)),
+      ],
+    );
+  }
+
+  // NOTE: Int and Float take priority over Symbol only when they match a lexeme of
+  //       the same length, so a sign must be followed by a digit to begin a number.
+  #[test]
+  fn lex_signs() {
+    check("+", Symbol);
+    check("-", Symbol);
+    check("+foo", Symbol);
+    check("-foo", Symbol);
+    check("+.", Symbol);
+    check("+-1", Symbol);
+    check("+1a", Symbol);
+    check("1+", Symbol);
+
+    check("+1", Int);
+    check("-1", Int);
+    check("+.5", Float);
+    check("-1.", Float);
+  }
+
+  #[test]
+  fn lex_keyword() {
+    check(":foo", Keyword);
+    check(":long-keyword-name", Keyword);
+    check("::foo", Keyword);
+
+    // A colon only begins a keyword if it is at the start of a lexeme.
+    check("a:b", Symbol);
+    check("a:", Symbol);
+    check(":", Symbol);
   }
 
   #[test]
@@ -191,6 +786,32 @@ mod tests {
 
     check("+6", Int);
     check("-1", Int);
+
+    check("#b101", Int);
+    check("#o-17", Int);
+    check("#D42", Int);
+    check("#xfF", Int);
+  }
+
+  #[test]
+  fn lex_float() {
+    check("0.1", Float);
+    check("1.", Float);
+    check(".5", Float);
+    check("-2.5", Float);
+    check("+2.5", Float);
+    check("1e300", Float);
+    check("1.5E-7", Float);
+    check("+inf.0", Float);
+    check("-inf.0", Float);
+    check("+nan.0", Float);
+    check("-nan.0", Float);
+
+    check(".", Dot);
+    check("...", Symbol);
+    check("inf.0", Symbol);
+    check("+inf", Symbol);
+    check("+inf.00", Symbol);
   }
 
   #[test]
@@ -201,6 +822,88 @@ mod tests {
     check("#false", Bool);
   }
 
+  #[test]
+  fn lex_char() {
+    check("#\\a", Char);
+    check("#\\(", Char);
+    check("#\\λ", Char);
+    check("#\\space", Char);
+    check("#\\x41", Char);
+  }
+
+  #[test]
+  fn lex_digit_separators() {
+    let kinds = |input| {
+      let options =
+        LexerOptions { allow_digit_separators: true, ..LexerOptions::default() };
+      Lexer::with_options(input, &options).map(|token| token.kind).collect::<Vec<_>>()
+    };
+
+    assert_eq!(kinds("1_000 -1_000_000 1_0.2_5 1e1_0"), [Int, Int, Float, Float]);
+    assert_eq!(kinds("_1 1_ 1__0 1_.0"), [Invalid, Invalid, Invalid, Invalid]);
+    // Symbols that do not represent numbers are unaffected.
+    assert_eq!(kinds("_ a_1 1_a"), [Symbol, Symbol, Symbol]);
+    // Underscores are not permitted by default.
+    assert_eq!(
+      Lexer::new("1_000").map(|token| token.kind).collect::<Vec<_>>(),
+      [Symbol]
+    );
+  }
+
+  #[test]
+  fn lex_datum_labels() {
+    check("#0=", LabelDef);
+    check("#12=", LabelDef);
+    check("#0#", LabelRef);
+    check("#12#", LabelRef);
+  }
+
+  #[test]
+  fn lex_datum_comment() {
+    check("#;", DatumComment);
+  }
+
+  #[test]
+  fn lex_unsupported_reader_syntax() {
+    check("#(", UnsupportedReaderSyntax);
+    check("#u8(", UnsupportedReaderSyntax);
+    check("#foo", UnsupportedReaderSyntax);
+  }
+
+  #[test]
+  fn remaining_input() {
+    let mut lexer = Lexer::new("(foo bar)");
+    assert_eq!(lexer.remaining(), "(foo bar)");
+
+    lexer.next();
+    assert_eq!(lexer.remaining(), "foo bar)");
+
+    lexer.next();
+    assert_eq!(lexer.remaining(), " bar)");
+
+    lexer.by_ref().for_each(drop);
+    assert!(lexer.remaining().is_empty());
+  }
+
+  #[test]
+  fn emit_eof() {
+    let mut lexer = Lexer::new("(a) ").with_eof();
+    assert_eq!(lexer.by_ref().take(3).count(), 3);
+    assert_eq!(
+      lexer.next(),
+      Some(Token { kind: Eof, lexeme: "", span: Span { start: 4, end: 4 } }),
+    );
+    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next(), None);
+
+    // Without opting in, no Eof token should be emitted.
+    assert_eq!(Lexer::new("(a) ").last().map(|t| t.kind), Some(RParen));
+
+    let mut lexer = Lexer::new("").with_eof();
+    assert_eq!(lexer.next().map(|t| t.kind), Some(Eof));
+    assert_eq!(lexer.next(), None);
+  }
+
   #[test]
   fn ignore_whitespace() {
     let mut lexer = TokenKind::lexer(" ");
@@ -216,6 +919,63 @@ mod tests {
     assert_eq!(lexer.next(), None);
   }
 
+  #[test]
+  fn forbid_tabs() {
+    let options = LexerOptions { forbid_tabs: false, ..LexerOptions::default() };
+    let kinds: Vec<_> =
+      Lexer::with_options("(a\tb)", &options).map(|t| t.kind).collect();
+    assert_eq!(kinds, [LParen, Symbol, Symbol, RParen]);
+
+    let options = LexerOptions { forbid_tabs: true, ..LexerOptions::default() };
+    let tokens: Vec<_> = Lexer::with_options("(a\tb)", &options).collect();
+    assert_eq!(tokens[2].kind, Tab);
+    assert_eq!(tokens[2].span, Span { start: 2, end: 3 });
+  }
+
+  #[test]
+  fn commas_as_whitespace() {
+    let options = LexerOptions { commas_as_whitespace: true, ..LexerOptions::default() };
+    let kinds: Vec<_> =
+      Lexer::with_options("(a, b ,@c)", &options).map(|t| t.kind).collect();
+    assert_eq!(kinds, [LParen, Symbol, Symbol, Invalid, Symbol, RParen]);
+  }
+
+  #[test]
+  fn custom_symbol_chars() {
+    let lex = |symbol_char| {
+      let options = LexerOptions { symbol_char, ..LexerOptions::default() };
+      Lexer::with_options("(a.b c/d &e)", &options)
+        .map(|t| (t.kind, t.lexeme))
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      lex(None),
+      [
+        (LParen, "("),
+        (Symbol, "a.b"),
+        (Symbol, "c/d"),
+        (Invalid, "&"),
+        (Symbol, "e"),
+        (RParen, ")")
+      ],
+    );
+    assert_eq!(
+      lex(Some(|c| c.is_alphanumeric() || c == '&')),
+      [
+        (LParen, "("),
+        (Symbol, "a"),
+        (Invalid, "."),
+        (Symbol, "b"),
+        (Symbol, "c"),
+        (Invalid, "/"),
+        (Symbol, "d"),
+        (Symbol, "&e"),
+        (RParen, ")"),
+      ],
+    );
+  }
+
   #[test]
   fn ignore_comments() {
     let mut lexer = TokenKind::lexer("; Hi!");
@@ -227,4 +987,273 @@ mod tests {
     let mut lexer = TokenKind::lexer("; Hi!\r\n");
     assert_eq!(lexer.next(), None);
   }
+
+  #[test]
+  fn lex_spans() {
+    check_spanned(
+      "(+ 1 22)",
+      &[
+        (LParen, span(0, 1)),
+        (Symbol, span(1, 2)),
+        (Int, span(3, 4)),
+        (Int, span(5, 7)),
+        (RParen, span(7, 8)),
+      ],
+    );
+    check_spanned("  foo\n\t bar  ", &[(Symbol, span(2, 5)), (Symbol, span(8, 11))]);
+    check_spanned(
+      "; Hi!\r\n'a ; b\n\"c\"",
+      &[(Quote, span(7, 8)), (Symbol, span(8, 9)), (String, span(14, 17))],
+    );
+    // Spans are measured in bytes rather than characters.
+    check_spanned(
+      "λ ,@μ",
+      &[(Symbol, span(0, 2)), (UnquoteSplicing, span(3, 5)), (Symbol, span(5, 7))],
+    );
+    check_spanned(
+      "#;#0=x",
+      &[(DatumComment, span(0, 2)), (LabelDef, span(2, 5)), (Symbol, span(5, 6))],
+    );
+    check_spanned("", &[]);
+  }
+
+  #[test]
+  fn emit_comments() {
+    let options = LexerOptions { emit_comments: true, ..LexerOptions::default() };
+    let tokens: Vec<_> = Lexer::with_options("a ; Hi!\r\nb", &options).collect();
+    assert_eq!(tokens[1].kind, Comment);
+    assert_eq!(tokens[1].lexeme, "; Hi!");
+    assert_eq!(tokens[2].kind, Symbol);
+  }
+
+  #[test]
+  fn emit_comments_and_eof() {
+    let options =
+      LexerOptions { emit_comments: true, emit_eof: true, ..LexerOptions::default() };
+
+    // The Eof token should follow a trailing comment rather than replace it.
+    let tokens: Vec<_> = Lexer::with_options("(a) ; done", &options)
+      .map(|token| (token.kind, token.span))
+      .collect();
+    assert_eq!(
+      tokens,
+      [
+        (LParen, span(0, 1)),
+        (Symbol, span(1, 2)),
+        (RParen, span(2, 3)),
+        (Comment, span(4, 10)),
+        (Eof, span(10, 10))
+      ],
+    );
+
+    let kinds: Vec<_> =
+      Lexer::with_options("; only\n", &options).map(|token| token.kind).collect();
+    assert_eq!(kinds, [Comment, Eof]);
+
+    // Either option alone should not imply the other.
+    let options = LexerOptions { emit_eof: true, ..LexerOptions::default() };
+    let kinds: Vec<_> =
+      Lexer::with_options("a ; b", &options).map(|token| token.kind).collect();
+    assert_eq!(kinds, [Symbol, Eof]);
+  }
+
+  #[test]
+  fn lex_heredoc() {
+    let options = LexerOptions { allow_heredocs: true, ..LexerOptions::default() };
+    let lex = |input| {
+      Lexer::with_options(input, &options)
+        .map(|token| (token.kind, token.span))
+        .collect::<Vec<_>>()
+    };
+
+    let input = "(#<<END\nfoo\n  END\nEND\n)";
+    assert_eq!(
+      lex(input),
+      [(LParen, span(0, 1)), (HereDoc, span(1, 21)), (RParen, span(22, 23))]
+    );
+    // The terminating line may end with a carriage return.
+    assert_eq!(
+      lex("#<<END\r\na\r\nEND\r\nb"),
+      [(HereDoc, span(0, 14)), (Symbol, span(16, 17))]
+    );
+    // A here-doc without a terminating line extends to the end of the input.
+    assert_eq!(lex("#<<END\na\nEN"), [(HereDoc, span(0, 11))]);
+    assert_eq!(lex("#<<END"), [(HereDoc, span(0, 6))]);
+    // A here-doc must have a tag.
+    assert_eq!(lex("#<<\na"), [(Invalid, span(0, 3)), (Symbol, span(4, 5))]);
+
+    // Without opting in, here-docs should remain unsupported.
+    assert_eq!(
+      Lexer::new("#<<END").next().map(|t| t.kind),
+      Some(UnsupportedReaderSyntax)
+    );
+  }
+
+  #[test]
+  fn classify_kinds() {
+    let kinds = TokenKind::all();
+    // NOTE: This match ensures that the test below is revisited with each new kind.
+    for &kind in kinds {
+      match kind {
+        LParen
+        | RParen
+        | LBracket
+        | RBracket
+        | LBrace
+        | RBrace
+        | Quote
+        | Quasiquote
+        | Unquote
+        | UnquoteSplicing
+        | Dot
+        | Symbol
+        | PipeSymbol
+        | Keyword
+        | String
+        | Int
+        | Float
+        | HereDoc
+        | Bool
+        | Char
+        | LabelDef
+        | LabelRef
+        | DatumComment
+        | UnsupportedReaderSyntax
+        | Eof
+        | Comment
+        | Tab
+        | Invalid => {},
+      }
+    }
+
+    let classify = |predicate: fn(&TokenKind) -> bool| {
+      kinds.iter().copied().filter(predicate).collect::<Vec<_>>()
+    };
+    assert_eq!(
+      classify(TokenKind::is_atom),
+      [Symbol, PipeSymbol, Keyword, String, Int, Float, HereDoc, Bool, Char]
+    );
+    assert_eq!(classify(TokenKind::is_opener), [LParen, LBracket, LBrace]);
+    assert_eq!(classify(TokenKind::is_closer), [RParen, RBracket, RBrace]);
+    assert_eq!(
+      classify(TokenKind::is_delimiter),
+      [LParen, RParen, LBracket, RBracket, LBrace, RBrace]
+    );
+  }
+
+  #[test]
+  fn enumerate_kinds() {
+    // This match has no wildcard arm, so adding a kind fails to compile until it is
+    // given an index here. Since `Invalid` is declared last, the assertions below then
+    // require the new kind to be listed.
+    let index = |kind: TokenKind| match kind {
+      LParen => 0,
+      RParen => 1,
+      LBracket => 2,
+      RBracket => 3,
+      LBrace => 4,
+      RBrace => 5,
+      Quote => 6,
+      Quasiquote => 7,
+      Unquote => 8,
+      UnquoteSplicing => 9,
+      Dot => 10,
+      Symbol => 11,
+      PipeSymbol => 12,
+      Keyword => 13,
+      String => 14,
+      Int => 15,
+      Float => 16,
+      HereDoc => 17,
+      Bool => 18,
+      Char => 19,
+      LabelDef => 20,
+      LabelRef => 21,
+      DatumComment => 22,
+      UnsupportedReaderSyntax => 23,
+      Eof => 24,
+      Comment => 25,
+      Tab => 26,
+      Invalid => 27,
+    };
+
+    // Every kind should be listed exactly once and in order of declaration.
+    let kinds = TokenKind::all();
+    assert_eq!(kinds.len(), index(Invalid) + 1);
+    for (i, &kind) in kinds.iter().enumerate() {
+      assert_eq!(index(kind), i, "{kind:?}");
+      assert_eq!(kind as usize, i, "{kind:?}");
+    }
+
+    for kind in kinds {
+      assert!(!kind.pattern_description().is_empty(), "{kind:?}");
+    }
+    assert_eq!(LBrace.pattern_description(), "`{`");
+  }
+
+  #[test]
+  fn find_token_at() {
+    const INPUT: &str = "(define foo \"a b\") ; c";
+
+    let kind_at = |pos| token_at(INPUT, pos).map(|token| (token.kind, token.lexeme));
+    assert_eq!(kind_at(0), Some((LParen, "(")));
+    // Within a symbol.
+    assert_eq!(kind_at(1), Some((Symbol, "define")));
+    assert_eq!(kind_at(6), Some((Symbol, "define")));
+    assert_eq!(kind_at(9), Some((Symbol, "foo")));
+    // Within a string, including at its whitespace.
+    assert_eq!(kind_at(12), Some((String, "\"a b\"")));
+    assert_eq!(kind_at(14), Some((String, "\"a b\"")));
+    assert_eq!(kind_at(17), Some((RParen, ")")));
+    // Within whitespace and comments.
+    assert_eq!(kind_at(7), None);
+    assert_eq!(kind_at(18), None);
+    assert_eq!(kind_at(21), None);
+    assert_eq!(kind_at(INPUT.len()), None);
+  }
+
+  #[test]
+  fn leading_trivia() {
+    fn trivia(lexer: Lexer<'_>) -> Vec<(&str, &str)> {
+      lexer
+        .with_trivia()
+        .map(|trivia| (trivia.token.lexeme, trivia.leading_ws))
+        .collect()
+    }
+
+    assert_eq!(
+      trivia(Lexer::new("(a\n  b)")),
+      [("(", ""), ("a", ""), ("b", "\n  "), (")", "")],
+    );
+    // Comments that are skipped are trivia too, as is the end of the input.
+    assert_eq!(
+      trivia(Lexer::new(" a ; c\n\tb \n").with_eof()),
+      [("a", " "), ("b", " ; c\n\t"), ("", " \n")],
+    );
+
+    let options = LexerOptions { emit_comments: true, ..LexerOptions::default() };
+    assert_eq!(
+      trivia(Lexer::with_options("a ; c\nb", &options)),
+      [("a", ""), ("; c", " "), ("b", "\n")],
+    );
+
+    // Trivia starts after any tokens that were consumed before.
+    let mut lexer = Lexer::new("(a  b)");
+    assert_eq!(lexer.nth(1).map(|token| token.lexeme), Some("a"));
+    assert_eq!(trivia(lexer), [("b", "  "), (")", "")]);
+  }
+
+  #[test]
+  fn display_token() {
+    let tokens: Vec<_> = Lexer::new("(foo \"a\\\"b\")").collect();
+
+    assert_eq!(tokens[1].to_string(), "symbol");
+    assert_eq!(format!("{:#}", tokens[1]), "symbol \"foo\" @1..4");
+    assert_eq!(format!("{:#}", tokens[0]), "`(` \"(\" @0..1");
+    // The lexeme is escaped so that it remains readable.
+    assert_eq!(
+      format!("{:#}", tokens[2]),
+      format!("{} {:?} @5..11", String, "\"a\\\"b\"")
+    );
+  }
 }
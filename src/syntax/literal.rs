@@ -0,0 +1,421 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Decoding and encoding of literal atoms.
+
+use std::fmt::{self, Write};
+
+use crate::syntax::{self, ErrorKind, Lexer, Radix, SExpr, Span, TokenKind};
+
+/// A specialisation of [`Result`](std::result::Result) for brevity when writing return
+/// types for literal decoding functions.
+type Result<T> = std::result::Result<T, syntax::Error>;
+
+impl SExpr {
+  /// Create a string atom from the raw contents of a string literal, excluding the
+  /// surrounding quotes, by decoding any escape sequences.
+  ///
+  /// The span of any error is relative to the start of `raw`.
+  pub fn try_string(raw: &str) -> Result<SExpr> {
+    decode_string(raw).map(SExpr::String)
+  }
+
+  /// Create a symbol atom from the raw contents of a symbol written between pipes,
+  /// excluding the surrounding pipes, by decoding any escape sequences.
+  ///
+  /// The escape sequences are the same as those of a string literal, so that `\|` is
+  /// a literal pipe. The span of any error is relative to the start of `raw`.
+  pub fn try_symbol(raw: &str) -> Result<SExpr> {
+    decode_string(raw).map(SExpr::Symbol)
+  }
+
+  /// Create a string atom from the lexeme of a here-doc string, such as
+  /// `#<<END\nfoo\nEND`, whose lines are taken verbatim without decoding any escape
+  /// sequences.
+  ///
+  /// The line ending before the terminating line is not part of the string. If the
+  /// here-doc is not terminated, then an error is returned whose span covers the
+  /// opening `#<<` and its tag, relative to the start of `lexeme`.
+  pub fn try_heredoc(lexeme: &str) -> Result<SExpr> {
+    let (first, rest) = lexeme.split_once('\n').unwrap_or((lexeme, ""));
+    let opening = first.trim_end_matches('\r');
+    let tag = opening.strip_prefix("#<<").unwrap_or_default();
+
+    // NOTE: The line ending before the terminating line is stripped along with it.
+    let body = if rest == tag {
+      Some("")
+    } else {
+      rest
+        .strip_suffix(tag)
+        .and_then(|body| body.strip_suffix('\n'))
+        .map(|body| body.strip_suffix('\r').unwrap_or(body))
+    };
+    let Some(body) = body.filter(|_| !tag.is_empty()) else {
+      return Err(syntax::Error {
+        span: Span { start: 0, end: opening.len() },
+        kind: ErrorKind::UnterminatedHereDoc { tag: tag.to_string() },
+      });
+    };
+
+    Ok(SExpr::String(body.to_string()))
+  }
+
+  /// Create a character atom from the lexeme of a character literal, such as `#\a`,
+  /// `#\space`, or `#\x41`.
+  ///
+  /// The span of any error is relative to the start of `lexeme`.
+  pub fn try_char(lexeme: &str) -> Result<SExpr> {
+    let invalid = || syntax::Error {
+      span: Span { start: 0, end: lexeme.len() },
+      kind: ErrorKind::InvalidCharacter { found: lexeme.to_string() },
+    };
+
+    let rest = lexeme.strip_prefix("#\\").ok_or_else(invalid)?;
+    let mut chars = rest.chars();
+    let c = match (chars.next(), chars.as_str()) {
+      (Some(c), "") => c,
+      _ => match CHAR_NAMES.iter().find(|&&(name, _)| name == rest) {
+        Some(&(_, c)) => c,
+        None => match rest.strip_prefix(['x', 'X']).map(decode_hex_escape) {
+          Some(Ok((c, len))) if len == rest.len() - 1 => c,
+          _ => return Err(invalid()),
+        },
+      },
+    };
+
+    Ok(SExpr::Char(c))
+  }
+
+  /// Create an integer atom from the lexeme of an integer literal, which may begin
+  /// with a radix prefix such as `#x`.
+  ///
+  /// The span of any error is relative to the start of `lexeme`.
+  pub fn try_int(lexeme: &str) -> Result<SExpr> {
+    let span = Span { start: 0, end: lexeme.len() };
+    let invalid = syntax::Error { span, kind: ErrorKind::InvalidToken };
+
+    let (radix, number) = match lexeme.strip_prefix('#') {
+      Some(rest) => {
+        let mut chars = rest.chars();
+        let radix = chars.next().and_then(Radix::from_prefix).ok_or(invalid.clone())?;
+        (radix, chars.as_str())
+      },
+      None => (Radix::Decimal, lexeme),
+    };
+    let digits = number.strip_prefix(['+', '-']).unwrap_or(number);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix.base())) {
+      return Err(invalid);
+    }
+
+    // NOTE: Since the lexeme has been validated, the only possible failure is overflow.
+    match i32::from_str_radix(number, radix.base()) {
+      Ok(value) => Ok(SExpr::Int(value, radix)),
+      Err(_) => Err(syntax::Error { span, kind: ErrorKind::IntegerOverflow }),
+    }
+  }
+}
+
+/// The names of the characters that may be written as `#\name`, as defined by R7RS.
+const CHAR_NAMES: [(&str, char); 9] = [
+  ("alarm", '\u{7}'),
+  ("backspace", '\u{8}'),
+  ("delete", '\u{7f}'),
+  ("escape", '\u{1b}'),
+  ("newline", '\n'),
+  ("null", '\0'),
+  ("return", '\r'),
+  ("space", ' '),
+  ("tab", '\t'),
+];
+
+/// Decode the hexadecimal scalar value at the start of the body of a hexadecimal
+/// escape, such as the `41` in either `\x41;` or `#\x41`.
+///
+/// This returns the decoded character along with the number of bytes of hexadecimal
+/// digits that were consumed, which is every leading hexadecimal digit in `src`. The
+/// span of any error is relative to the start of `src`.
+pub(crate) fn decode_hex_escape(src: &str) -> Result<(char, usize)> {
+  let len = src.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(src.len());
+  let digits = &src[..len];
+
+  // NOTE: Parsing fails if there are no digits or the value overflows, and the
+  //       conversion fails if the value is beyond the Unicode range or a surrogate.
+  match u32::from_str_radix(digits, 16).ok().and_then(char::from_u32) {
+    Some(c) => Ok((c, len)),
+    None => Err(syntax::Error {
+      span: Span { start: 0, end: len },
+      kind: ErrorKind::InvalidEscape { sequence: digits.to_string() },
+    }),
+  }
+}
+
+/// Decode the escape sequences in the raw contents of a string literal.
+///
+/// The supported escape sequences are those of R7RS: `\a`, `\b`, `\t`, `\n`, `\r`,
+/// `\"`, `\\`, `\|`, hexadecimal escapes of the form `\x41;`, and line continuations,
+/// which consist of a backslash followed by a line ending, with optional intraline
+/// whitespace on either side of the line ending.
+pub(crate) fn decode_string(raw: &str) -> Result<String> {
+  let mut output = String::with_capacity(raw.len());
+  let mut chars = raw.char_indices().peekable();
+
+  while let Some((start, c)) = chars.next() {
+    if c != '\\' {
+      output.push(c);
+      continue;
+    }
+
+    let invalid = |end: usize| syntax::Error {
+      span: Span { start, end },
+      kind: ErrorKind::InvalidEscape { sequence: raw[start..end].to_string() },
+    };
+
+    let Some((i, c)) = chars.next() else { return Err(invalid(raw.len())) };
+    match c {
+      'a' => output.push('\u{7}'),
+      'b' => output.push('\u{8}'),
+      't' => output.push('\t'),
+      'n' => output.push('\n'),
+      'r' => output.push('\r'),
+      '"' | '\\' | '|' => output.push(c),
+      'x' => {
+        let Some(len) = raw[i + 1..].find(';') else { return Err(invalid(i + 1)) };
+        let end = i + 1 + len + 1;
+        match decode_hex_escape(&raw[i + 1..end - 1]) {
+          Ok((c, consumed)) if consumed == len => output.push(c),
+          _ => return Err(invalid(end)),
+        }
+        // Skip over the hexadecimal digits and the terminating semicolon.
+        while chars.next_if(|&(j, _)| j < end).is_some() {}
+      },
+      ' ' | '\t' | '\r' | '\n' => {
+        // Skip any intraline whitespace that precedes the line ending.
+        let (mut i, mut c) = (i, c);
+        while c == ' ' || c == '\t' {
+          match chars.next() {
+            Some(next) => (i, c) = next,
+            None => return Err(invalid(raw.len())),
+          }
+        }
+        match c {
+          '\n' => {},
+          '\r' => {
+            chars.next_if(|&(_, c)| c == '\n');
+          },
+          _ => return Err(invalid(i + c.len_utf8())),
+        }
+        // Skip any intraline whitespace that follows the line ending.
+        while chars.next_if(|&(_, c)| c == ' ' || c == '\t').is_some() {}
+      },
+      _ => return Err(invalid(i + c.len_utf8())),
+    }
+  }
+
+  Ok(output)
+}
+
+/// Write a character as a character literal.
+///
+/// Named characters are written by name, and any other control or whitespace
+/// characters are written as hexadecimal escapes so that they remain visible.
+pub(crate) fn write_char(f: &mut impl Write, c: char) -> fmt::Result {
+  match CHAR_NAMES.iter().find(|&&(_, named)| named == c) {
+    Some((name, _)) => write!(f, "#\\{name}"),
+    None if c.is_control() || c.is_whitespace() => write!(f, "#\\x{:x}", c as u32),
+    None => write!(f, "#\\{c}"),
+  }
+}
+
+/// Write a string as a string literal, escaping any characters that require it.
+pub(crate) fn write_string(f: &mut impl Write, string: &str) -> fmt::Result {
+  write_delimited(f, string, '"')
+}
+
+/// Write the name of a symbol, writing it between pipes, as in `|hello world|`, if it
+/// would not otherwise be read back as the same symbol.
+pub(crate) fn write_symbol(f: &mut impl Write, name: &str) -> fmt::Result {
+  if is_bare_symbol(name) {
+    f.write_str(name)
+  } else {
+    write_delimited(f, name, '|')
+  }
+}
+
+/// Check whether a name would be read back as a symbol with the same name if it were
+/// written verbatim.
+fn is_bare_symbol(name: &str) -> bool {
+  let mut lexer = Lexer::new(name);
+  match (lexer.next(), lexer.next()) {
+    (Some(token), None) => token.kind == TokenKind::Symbol && token.lexeme == name,
+    _ => false,
+  }
+}
+
+/// Write a string between a pair of delimiters, escaping any characters that require
+/// it, including the delimiter itself.
+fn write_delimited(f: &mut impl Write, string: &str, delimiter: char) -> fmt::Result {
+  f.write_char(delimiter)?;
+  for c in string.chars() {
+    match c {
+      '\\' => f.write_str("\\\\")?,
+      c if c == delimiter => write!(f, "\\{c}")?,
+      '\t' => f.write_str("\\t")?,
+      '\n' => f.write_str("\\n")?,
+      '\r' => f.write_str("\\r")?,
+      c if c.is_control() => write!(f, "\\x{:x};", c as u32)?,
+      c => f.write_char(c)?,
+    }
+  }
+  f.write_char(delimiter)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn try_string() -> Result<()> {
+    let string = |s: &str| SExpr::String(s.to_string());
+
+    assert_eq!(SExpr::try_string("foo")?, string("foo"));
+    assert_eq!(SExpr::try_string(r#"a\"b\\c\|d"#)?, string("a\"b\\c|d"));
+    assert_eq!(SExpr::try_string(r"\a\b\t\n\r")?, string("\u{7}\u{8}\t\n\r"));
+    assert_eq!(SExpr::try_string(r"\x41;\x3bb;")?, string("Aλ"));
+    assert_eq!(SExpr::try_string("a\\  \n  b")?, string("ab"));
+    assert_eq!(SExpr::try_string("a\\\r\nb")?, string("ab"));
+
+    let error = |start, end, sequence: &str| syntax::Error {
+      span: Span { start, end },
+      kind: ErrorKind::InvalidEscape { sequence: sequence.to_string() },
+    };
+    assert_eq!(SExpr::try_string(r"a\qb"), Err(error(1, 3, r"\q")));
+    assert_eq!(SExpr::try_string(r"\x41"), Err(error(0, 2, r"\x")));
+    assert_eq!(SExpr::try_string(r"\x;"), Err(error(0, 3, r"\x;")));
+    assert_eq!(SExpr::try_string(r"\xd800;"), Err(error(0, 7, r"\xd800;")));
+    assert_eq!(SExpr::try_string(r"\xzz;"), Err(error(0, 5, r"\xzz;")));
+    assert_eq!(SExpr::try_string("\\  b"), Err(error(0, 4, "\\  b")));
+    assert_eq!(SExpr::try_string("\\"), Err(error(0, 1, "\\")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn hex_escape() -> Result<()> {
+    assert_eq!(decode_hex_escape("41")?, ('A', 2));
+    assert_eq!(decode_hex_escape("3BB;")?, ('λ', 3));
+    assert_eq!(decode_hex_escape("10ffff")?, ('\u{10ffff}', 6));
+    // Leading zeros are permitted, however many there are.
+    assert_eq!(decode_hex_escape("0000000041")?, ('A', 10));
+
+    let error = |end, sequence: &str| syntax::Error {
+      span: Span { start: 0, end },
+      kind: ErrorKind::InvalidEscape { sequence: sequence.to_string() },
+    };
+    assert_eq!(decode_hex_escape(";"), Err(error(0, "")));
+    // Overlong values are beyond the Unicode range or do not even fit in 32 bits.
+    assert_eq!(decode_hex_escape("110000"), Err(error(6, "110000")));
+    assert_eq!(decode_hex_escape("123456789;"), Err(error(9, "123456789")));
+    // Surrogates are not Unicode scalar values.
+    assert_eq!(decode_hex_escape("d800"), Err(error(4, "d800")));
+    assert_eq!(decode_hex_escape("DFFF;"), Err(error(4, "DFFF")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn try_char() -> Result<()> {
+    assert_eq!(SExpr::try_char("#\\a")?, SExpr::Char('a'));
+    assert_eq!(SExpr::try_char("#\\(")?, SExpr::Char('('));
+    assert_eq!(SExpr::try_char("#\\x")?, SExpr::Char('x'));
+    assert_eq!(SExpr::try_char("#\\λ")?, SExpr::Char('λ'));
+    assert_eq!(SExpr::try_char("#\\space")?, SExpr::Char(' '));
+    assert_eq!(SExpr::try_char("#\\null")?, SExpr::Char('\0'));
+    assert_eq!(SExpr::try_char("#\\x41")?, SExpr::Char('A'));
+    assert_eq!(SExpr::try_char("#\\X3bb")?, SExpr::Char('λ'));
+
+    let error = |found: &str| syntax::Error {
+      span: Span { start: 0, end: found.len() },
+      kind: ErrorKind::InvalidCharacter { found: found.to_string() },
+    };
+    assert_eq!(SExpr::try_char("#\\foo"), Err(error("#\\foo")));
+    assert_eq!(SExpr::try_char("#\\Space"), Err(error("#\\Space")));
+    assert_eq!(SExpr::try_char("#\\x41;"), Err(error("#\\x41;")));
+    assert_eq!(SExpr::try_char("#\\xd800"), Err(error("#\\xd800")));
+    assert_eq!(SExpr::try_char("#\\"), Err(error("#\\")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn try_int() -> Result<()> {
+    assert_eq!(SExpr::try_int("42")?, SExpr::Int(42, Radix::Decimal));
+    assert_eq!(SExpr::try_int("-2147483648")?, SExpr::Int(i32::MIN, Radix::Decimal));
+    assert_eq!(SExpr::try_int("+7")?, SExpr::Int(7, Radix::Decimal));
+    assert_eq!(SExpr::try_int("#b-101")?, SExpr::Int(-5, Radix::Binary));
+    assert_eq!(SExpr::try_int("#o17")?, SExpr::Int(15, Radix::Octal));
+    assert_eq!(SExpr::try_int("#d9")?, SExpr::Int(9, Radix::Decimal));
+    assert_eq!(SExpr::try_int("#XfF")?, SExpr::Int(255, Radix::Hexadecimal));
+
+    let error = |end, kind| syntax::Error { span: Span { start: 0, end }, kind };
+    assert_eq!(SExpr::try_int("2147483648"), Err(error(10, ErrorKind::IntegerOverflow)));
+    assert_eq!(SExpr::try_int("12a"), Err(error(3, ErrorKind::InvalidToken)));
+    assert_eq!(SExpr::try_int("-"), Err(error(1, ErrorKind::InvalidToken)));
+    assert_eq!(SExpr::try_int(""), Err(error(0, ErrorKind::InvalidToken)));
+    assert_eq!(SExpr::try_int("#b2"), Err(error(3, ErrorKind::InvalidToken)));
+    assert_eq!(SExpr::try_int("#z1"), Err(error(3, ErrorKind::InvalidToken)));
+    assert_eq!(SExpr::try_int("#x"), Err(error(2, ErrorKind::InvalidToken)));
+    assert_eq!(SExpr::try_int("#x80000000"), Err(error(10, ErrorKind::IntegerOverflow)));
+
+    // Non-digit characters, including those of the special floating-point literals,
+    // should never be accepted.
+    for lexeme in ["inf", "+inf.0", "-nan.0", "NaN", "1.0", "1e3", "#x+inf.0", "1_0"] {
+      assert_eq!(
+        SExpr::try_int(lexeme),
+        Err(error(lexeme.len(), ErrorKind::InvalidToken))
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn try_heredoc() -> Result<()> {
+    let string = |s: &str| SExpr::String(s.to_string());
+    assert_eq!(SExpr::try_heredoc("#<<END\nfoo\n\\n\"\nEND")?, string("foo\n\\n\""));
+    assert_eq!(SExpr::try_heredoc("#<<END\nEND")?, string(""));
+    assert_eq!(SExpr::try_heredoc("#<<END\n\nEND")?, string(""));
+    assert_eq!(SExpr::try_heredoc("#<<END\r\na\r\nb\r\nEND")?, string("a\r\nb"));
+
+    let error = |end| syntax::Error {
+      span: Span { start: 0, end },
+      kind: ErrorKind::UnterminatedHereDoc { tag: "END".to_string() },
+    };
+    assert_eq!(SExpr::try_heredoc("#<<END"), Err(error(6)));
+    assert_eq!(SExpr::try_heredoc("#<<END\nfoo"), Err(error(6)));
+    assert_eq!(SExpr::try_heredoc("#<<END\r\nfooEND"), Err(error(6)));
+
+    Ok(())
+  }
+
+  #[test]
+  fn write_escaped_string() {
+    let mut output = String::new();
+    write_string(&mut output, "a\"b\\c\nd\u{7}λ").unwrap();
+    assert_eq!(output, r#""a\"b\\c\nd\x7;λ""#);
+  }
+
+  #[test]
+  fn write_char_literal() {
+    let written = |c| {
+      let mut output = String::new();
+      write_char(&mut output, c).unwrap();
+      output
+    };
+
+    assert_eq!(written('a'), "#\\a");
+    assert_eq!(written(' '), "#\\space");
+    assert_eq!(written('\u{7f}'), "#\\delete");
+    assert_eq!(written('\u{1}'), "#\\x1");
+    assert_eq!(written('\u{a0}'), "#\\xa0");
+  }
+}
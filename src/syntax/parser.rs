@@ -3,23 +3,625 @@
 
 //! Parser for Luna source code.
 
-use std::iter::Peekable;
+use std::{
+  borrow::Cow, collections::HashSet, fmt, iter::Peekable, num::TryFromIntError,
+};
 
-use crate::syntax::{self, Lexer, Span, TokenKind};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::syntax::{
+  self,
+  intern::fold_case,
+  literal::{write_char, write_string, write_symbol},
+  BracketKind, BytePos, Lexer, LexerOptions, Radix, Span, Token, TokenKind, MAX_POS,
+};
 
 /// A symbolic expression.
-#[derive(Eq, PartialEq, Debug)]
+///
+/// The derived [`PartialEq`] implementation is the default comparison, and compares
+/// expressions structurally, such that it also distinguishes between the ways in
+/// which the same value may be written, such as `(1 2)` and `[1 2]`, or `255` and
+/// `#xff`. Use [`SExpr::value_eq`] to compare expressions by value instead, or
+/// [`SExpr::structural_eq`] to compare them structurally such that NaNs are equal.
+#[derive(Clone, PartialEq, Debug)]
 pub enum SExpr {
   /// A symbol atom.
   Symbol(String),
+  /// A keyword atom, whose name excludes the leading colon.
+  Keyword(String),
   /// A string atom.
   String(String),
-  /// An integer atom.
-  Int(i32),
+  /// An integer atom, along with the radix in which it was written.
+  Int(i32, Radix),
+  /// A floating-point atom.
+  Float(f64),
   /// A Boolean atom.
   Bool(bool),
-  /// A list of symbolic expressions.
-  List(Vec<SExpr>),
+  /// A character atom.
+  Char(char),
+  /// A list of symbolic expressions, along with the kind of bracket that delimited it.
+  List(Vec<SExpr>, BracketKind),
+  /// A map of keys to values, which may contain duplicate keys.
+  Map(Vec<(SExpr, SExpr)>),
+  /// A datum labelled with `#n=` so that it may be referred to elsewhere.
+  Labelled(u32, Box<SExpr>),
+  /// A reference `#n#` to a labelled datum.
+  LabelRef(u32),
+  /// The text of an invalid token, along with its span, which is only produced if
+  /// [`ParseOptions::lenient_invalid`] is set.
+  Invalid {
+    /// The text of the token.
+    text: String,
+    /// The span of the token.
+    span: Span,
+  },
+}
+
+impl SExpr {
+  /// Get the name of this expression if it is a symbol.
+  pub fn as_symbol(&self) -> Option<&str> {
+    match self {
+      SExpr::Symbol(name) => Some(name),
+      _ => None,
+    }
+  }
+
+  /// Check whether this expression is a symbol whose name is equal to another ignoring
+  /// case, such that `Foo` and `foo` compare equal.
+  ///
+  /// The name of the symbol itself is left untouched, so it is still displayed in its
+  /// original case.
+  pub fn eq_ignore_case(&self, other: &str) -> bool {
+    self.as_symbol().is_some_and(|name| fold_case(name).eq(fold_case(other)))
+  }
+
+  /// Get the elements of this expression if it is a list, regardless of its brackets.
+  pub fn as_list(&self) -> Option<&[SExpr]> {
+    match self {
+      SExpr::List(list, _) => Some(list),
+      _ => None,
+    }
+  }
+
+  /// Check whether this expression evaluates to itself, as with numbers, Booleans,
+  /// strings, characters and keywords, such that it need not be quoted.
+  ///
+  /// Symbols and lists are not self-evaluating, and nor are maps, since their entries
+  /// are themselves evaluated. A labelled datum is self-evaluating if the datum is.
+  pub fn is_self_evaluating(&self) -> bool {
+    match self {
+      SExpr::Keyword(_)
+      | SExpr::String(_)
+      | SExpr::Int(..)
+      | SExpr::Float(_)
+      | SExpr::Bool(_)
+      | SExpr::Char(_) => true,
+      SExpr::Labelled(_, datum) => datum.is_self_evaluating(),
+      SExpr::Symbol(_)
+      | SExpr::List(..)
+      | SExpr::Map(_)
+      | SExpr::LabelRef(_)
+      | SExpr::Invalid { .. } => false,
+    }
+  }
+
+  /// Check whether this expression is a symbol, as with `symbol?` in Scheme.
+  ///
+  /// As with [`SExpr::is_self_evaluating`], a labelled datum is a symbol if the datum
+  /// is, and likewise for the other predicates.
+  pub fn is_symbol(&self) -> bool {
+    match self {
+      SExpr::Symbol(_) => true,
+      SExpr::Labelled(_, datum) => datum.is_symbol(),
+      _ => false,
+    }
+  }
+
+  /// Check whether this expression is a list, regardless of its brackets.
+  pub fn is_list(&self) -> bool {
+    match self {
+      SExpr::List(..) => true,
+      SExpr::Labelled(_, datum) => datum.is_list(),
+      _ => false,
+    }
+  }
+
+  /// Check whether this expression is the empty list, regardless of its brackets, as
+  /// with `null?` in Scheme.
+  pub fn is_nil(&self) -> bool {
+    match self {
+      SExpr::List(list, _) => list.is_empty(),
+      SExpr::Labelled(_, datum) => datum.is_nil(),
+      _ => false,
+    }
+  }
+
+  /// Destructure this expression if it is a list of exactly `N + 1` elements headed by
+  /// the given symbol, returning the `N` elements that follow the head.
+  ///
+  /// This is intended for matching special forms, such that `let [test, consequent,
+  /// alternative] = expr.as_form("if")?` destructures an `if` expression, for example.
+  pub fn as_form<const N: usize>(&self, head: &str) -> Option<[&SExpr; N]> {
+    match self.as_list()? {
+      [first, rest @ ..] if first.as_symbol() == Some(head) && rest.len() == N => {
+        Some(std::array::from_fn(|i| &rest[i]))
+      },
+      _ => None,
+    }
+  }
+
+  /// Get the quoted datum if this is a list of the form `(quote datum)`.
+  pub fn as_quoted(&self) -> Option<&SExpr> {
+    self.as_abbreviation("quote")
+  }
+
+  /// Get the quasiquoted datum if this is a list of the form `(quasiquote datum)`.
+  pub fn as_quasiquoted(&self) -> Option<&SExpr> {
+    self.as_abbreviation("quasiquote")
+  }
+
+  /// Get the unquoted datum if this is a list of the form `(unquote datum)`.
+  pub fn as_unquoted(&self) -> Option<&SExpr> {
+    self.as_abbreviation("unquote")
+  }
+
+  /// Get the spliced datum if this is a list of the form `(unquote-splicing datum)`.
+  pub fn as_unquote_spliced(&self) -> Option<&SExpr> {
+    self.as_abbreviation("unquote-splicing")
+  }
+
+  /// Get the keys that occur more than once if this is a map.
+  ///
+  /// Each duplicated key is returned once, in the order in which its first duplicate
+  /// occurs. This is useful for warning about maps whose later entries shadow earlier
+  /// ones.
+  pub fn duplicate_keys(&self) -> Vec<&SExpr> {
+    let SExpr::Map(entries) = self else { return Vec::new() };
+
+    let mut duplicates = Vec::new();
+    for (i, (key, _)) in entries.iter().enumerate() {
+      let is_duplicate = entries[..i].iter().any(|(other, _)| other == key);
+      if is_duplicate && !duplicates.contains(&key) {
+        duplicates.push(key);
+      }
+    }
+
+    duplicates
+  }
+
+  /// Check whether this expression is equal to another by value.
+  ///
+  /// Unlike the structural comparison of [`PartialEq`], this ignores the kind of
+  /// bracket that delimits each list, the radix in which each integer is written, and
+  /// the span of each invalid token, since none affects the meaning of an expression.
+  pub fn value_eq(&self, other: &SExpr) -> bool {
+    match (self, other) {
+      (SExpr::Int(lhs, _), SExpr::Int(rhs, _)) => lhs == rhs,
+      (SExpr::List(lhs, _), SExpr::List(rhs, _)) => {
+        lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(lhs, rhs)| lhs.value_eq(rhs))
+      },
+      (SExpr::Map(lhs), SExpr::Map(rhs)) => {
+        lhs.len() == rhs.len()
+          && lhs.iter().zip(rhs).all(|((lhs_key, lhs_value), (rhs_key, rhs_value))| {
+            lhs_key.value_eq(rhs_key) && lhs_value.value_eq(rhs_value)
+          })
+      },
+      (SExpr::Labelled(lhs_label, lhs), SExpr::Labelled(rhs_label, rhs)) => {
+        lhs_label == rhs_label && lhs.value_eq(rhs)
+      },
+      (SExpr::Invalid { text: lhs, .. }, SExpr::Invalid { text: rhs, .. }) => lhs == rhs,
+      (SExpr::List(..) | SExpr::Map(_) | SExpr::Labelled(..), _) => false,
+      _ => self == other,
+    }
+  }
+
+  /// Check whether this expression is structurally equal to another, comparing
+  /// floating-point atoms by their bits.
+  ///
+  /// This is the same comparison as [`PartialEq`], except that the derived
+  /// implementation follows IEEE 754 and so never considers a NaN to be equal to
+  /// itself, which makes it unsuitable for comparing parse trees. Here, `+nan.0` is
+  /// equal to `+nan.0`, but since the comparison is bitwise, `0.0` is no longer equal
+  /// to `-0.0`. Hence [`SExpr`] deliberately does not implement [`Eq`].
+  pub fn structural_eq(&self, other: &SExpr) -> bool {
+    match (self, other) {
+      (SExpr::Float(lhs), SExpr::Float(rhs)) => lhs.to_bits() == rhs.to_bits(),
+      (SExpr::List(lhs, lhs_bracket), SExpr::List(rhs, rhs_bracket)) => {
+        lhs_bracket == rhs_bracket
+          && lhs.len() == rhs.len()
+          && lhs.iter().zip(rhs).all(|(lhs, rhs)| lhs.structural_eq(rhs))
+      },
+      (SExpr::Map(lhs), SExpr::Map(rhs)) => {
+        lhs.len() == rhs.len()
+          && lhs.iter().zip(rhs).all(|((lhs_key, lhs_value), (rhs_key, rhs_value))| {
+            lhs_key.structural_eq(rhs_key) && lhs_value.structural_eq(rhs_value)
+          })
+      },
+      (SExpr::Labelled(lhs_label, lhs), SExpr::Labelled(rhs_label, rhs)) => {
+        lhs_label == rhs_label && lhs.structural_eq(rhs)
+      },
+      (SExpr::Float(_) | SExpr::List(..) | SExpr::Map(_) | SExpr::Labelled(..), _) => {
+        false
+      },
+      _ => self == other,
+    }
+  }
+
+  /// Get the datum wrapped by this expression if it is a two-element list headed by
+  /// the given keyword.
+  fn as_abbreviation(&self, keyword: &str) -> Option<&SExpr> {
+    self.as_form(keyword).map(|[datum]| datum)
+  }
+}
+
+/// The spelling with which to display Booleans.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub enum BoolStyle {
+  /// The short spellings `#t` and `#f`.
+  #[default]
+  Short,
+  /// The long spellings `#true` and `#false`.
+  Long,
+}
+
+/// The way in which to display symbols.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub enum SymbolStyle {
+  /// Write a symbol that would not be read back as the same symbol between pipes, as in
+  /// `|hello world|`, with any pipes and backslashes within it escaped.
+  #[default]
+  Quoted,
+  /// Write every symbol verbatim, even if it would not be read back as the same symbol.
+  Bare,
+}
+
+/// The conventions with which to display symbolic expressions.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub struct DisplayConfig {
+  /// The spelling with which to display Booleans.
+  pub bool_style: BoolStyle,
+  /// The way in which to display symbols.
+  pub symbol_style: SymbolStyle,
+}
+
+/// A symbolic expression paired with the conventions with which to display it, as
+/// returned by [`SExpr::display_with`].
+#[derive(Copy, Clone, Debug)]
+pub struct DisplayWith<'a> {
+  /// The symbolic expression to display.
+  sexpr: &'a SExpr,
+  /// The conventions with which to display it.
+  config: &'a DisplayConfig,
+}
+
+impl SExpr {
+  /// Get an object that displays this expression according to the given conventions.
+  ///
+  /// The [`Display`](fmt::Display) implementation for `SExpr` itself uses the default
+  /// conventions.
+  pub fn display_with<'a>(&'a self, config: &'a DisplayConfig) -> DisplayWith<'a> {
+    DisplayWith { sexpr: self, config }
+  }
+}
+
+impl fmt::Display for SExpr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.display_with(&DisplayConfig::default()))
+  }
+}
+
+impl fmt::Display for DisplayWith<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let config = self.config;
+    match self.sexpr {
+      SExpr::Symbol(name) => match config.symbol_style {
+        SymbolStyle::Quoted => write_symbol(f, name),
+        SymbolStyle::Bare => write!(f, "{name}"),
+      },
+      SExpr::Keyword(name) => write!(f, ":{name}"),
+      SExpr::String(string) => write_string(f, string),
+      SExpr::Int(value, radix) => {
+        let sign = if *value < 0 { "-" } else { "" };
+        let magnitude = value.unsigned_abs();
+        match radix {
+          Radix::Binary => write!(f, "#b{sign}{magnitude:b}"),
+          Radix::Octal => write!(f, "#o{sign}{magnitude:o}"),
+          Radix::Decimal => write!(f, "{value}"),
+          Radix::Hexadecimal => write!(f, "#x{sign}{magnitude:x}"),
+        }
+      },
+      SExpr::Float(value) => write!(f, "{}", format_float(*value)),
+      SExpr::Bool(value) => match (config.bool_style, value) {
+        (BoolStyle::Short, true) => write!(f, "#t"),
+        (BoolStyle::Short, false) => write!(f, "#f"),
+        (BoolStyle::Long, true) => write!(f, "#true"),
+        (BoolStyle::Long, false) => write!(f, "#false"),
+      },
+      SExpr::Char(c) => write_char(f, *c),
+      SExpr::List(list, bracket) => {
+        write!(f, "{}", bracket.opener())?;
+        for (i, sexpr) in list.iter().enumerate() {
+          if i > 0 {
+            write!(f, " ")?;
+          }
+          write!(f, "{}", sexpr.display_with(config))?;
+        }
+        write!(f, "{}", bracket.closer())
+      },
+      SExpr::Map(entries) => {
+        write!(f, "{{")?;
+        for (i, (key, value)) in entries.iter().enumerate() {
+          if i > 0 {
+            write!(f, " ")?;
+          }
+          write!(f, "{} {}", key.display_with(config), value.display_with(config))?;
+        }
+        write!(f, "}}")
+      },
+      SExpr::Labelled(label, datum) => {
+        write!(f, "#{label}={}", datum.display_with(config))
+      },
+      SExpr::LabelRef(label) => write!(f, "#{label}#"),
+      SExpr::Invalid { text, .. } => write!(f, "{text}"),
+    }
+  }
+}
+
+impl From<i32> for SExpr {
+  fn from(value: i32) -> Self {
+    SExpr::Int(value, Radix::Decimal)
+  }
+}
+
+impl TryFrom<i64> for SExpr {
+  type Error = TryFromIntError;
+
+  fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+    Ok(SExpr::Int(value.try_into()?, Radix::Decimal))
+  }
+}
+
+impl From<bool> for SExpr {
+  fn from(value: bool) -> Self {
+    SExpr::Bool(value)
+  }
+}
+
+/// Convert a string slice into a symbol atom.
+impl From<&str> for SExpr {
+  fn from(name: &str) -> Self {
+    SExpr::Symbol(name.to_string())
+  }
+}
+
+/// Convert an owned string into a string atom.
+impl From<String> for SExpr {
+  fn from(string: String) -> Self {
+    SExpr::String(string)
+  }
+}
+
+/// Collect symbolic expressions into a list delimited by round brackets.
+impl FromIterator<SExpr> for SExpr {
+  fn from_iter<I: IntoIterator<Item = SExpr>>(iter: I) -> Self {
+    SExpr::List(iter.into_iter().collect(), BracketKind::Paren)
+  }
+}
+
+/// Format a floating-point number such that it may be parsed back into the same value.
+///
+/// Finite values are formatted with the shortest representation that round-trips,
+/// and non-finite values are formatted as `+inf.0`, `-inf.0`, or `+nan.0` as in R7RS.
+pub(crate) fn format_float(value: f64) -> String {
+  if value.is_nan() {
+    "+nan.0".to_string()
+  } else if value.is_infinite() {
+    if value > 0.0 { "+inf.0" } else { "-inf.0" }.to_string()
+  } else {
+    ryu::Buffer::new().format_finite(value).to_string()
+  }
+}
+
+/// Options that control the behaviour of the parser.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+  /// Whether to apply Unicode Normalisation Form C (NFC) to the input before lexing.
+  ///
+  /// This ensures that symbols compare equal regardless of whether the source encodes
+  /// them with precomposed or decomposed characters. Note that when this option is
+  /// enabled, all spans refer to byte positions within the _normalised_ input rather
+  /// than the original input.
+  pub normalize_nfc: bool,
+  /// Whether to reject tab characters in the input with a
+  /// [`TabInSource`](syntax::ErrorKind::TabInSource) error.
+  pub forbid_tabs: bool,
+  /// Whether to treat commas as whitespace, as in EDN.
+  ///
+  /// This is mutually exclusive with the unquote abbreviations: when it is enabled,
+  /// `,` is never parsed as `unquote`, and `,@` is rejected as an invalid token.
+  pub commas_as_whitespace: bool,
+  /// A predicate that determines which characters may appear within a symbol, or
+  /// `None` to use the default set of characters required by R7RS.
+  ///
+  /// See [`LexerOptions::symbol_char`] for the exact rules.
+  pub symbol_char: Option<fn(char) -> bool>,
+  /// Whether to parse curly brackets as maps of the form `{key value ...}` rather than
+  /// as lists.
+  ///
+  /// This takes precedence over [`strict_brackets`](ParseOptions::strict_brackets).
+  pub braces_as_maps: bool,
+  /// Whether to restrict which brackets may delimit lists.
+  ///
+  /// When this option is enabled, the rules are as follows:
+  ///
+  /// - Lists may be delimited by either `(` and `)` or `[` and `]`, and each list must
+  ///   be closed by the bracket that matches its opener, as is always the case.
+  /// - Curly brackets are reserved for future syntax, so an opening `{` is rejected
+  ///   with a [`ReservedBracket`](syntax::ErrorKind::ReservedBracket) error.
+  pub strict_brackets: bool,
+  /// The maximum size of the input in bytes, or `None` for no limit.
+  ///
+  /// Inputs exceeding this limit are rejected with an
+  /// [`InputTooLarge`](syntax::ErrorKind::InputTooLarge) error before lexing begins.
+  pub max_bytes: Option<usize>,
+  /// The maximum length of an individual token in bytes, or `None` for no limit.
+  ///
+  /// Tokens exceeding this limit are rejected with a
+  /// [`TokenTooLong`](syntax::ErrorKind::TokenTooLong) error whose span covers the part
+  /// of the token beyond the limit.
+  pub max_token_len: Option<usize>,
+  /// The maximum number of elements in an individual list, or `None` for no limit.
+  ///
+  /// Lists exceeding this limit are rejected with a
+  /// [`ListTooLong`](syntax::ErrorKind::ListTooLong) error whose span covers the first
+  /// token of the element beyond the limit. The keys and values of a map each count as
+  /// an element.
+  pub max_list_len: Option<usize>,
+  /// Whether to parse unsupported `#`-prefixed reader syntax, such as `#foo`, as
+  /// symbols rather than rejecting it with an
+  /// [`UnsupportedReaderSyntax`](syntax::ErrorKind::UnsupportedReaderSyntax) error, or
+  /// with a [`ReservedSymbolStart`](syntax::ErrorKind::ReservedSymbolStart) error if it
+  /// looks like a symbol with a stray leading `#`.
+  ///
+  /// Supported reader syntax, such as `#t` and `#;`, is unaffected. Note that the
+  /// opening `#(` of a vector is parsed as a symbol too, which leaves its closing
+  /// bracket unmatched.
+  pub lenient_hash: bool,
+  /// Whether to parse invalid tokens into [`SExpr::Invalid`] nodes that capture their
+  /// text and span, rather than rejecting them with an
+  /// [`InvalidToken`](syntax::ErrorKind::InvalidToken) error.
+  ///
+  /// This allows tools such as editors to display what could not be parsed instead of
+  /// losing it.
+  pub lenient_invalid: bool,
+  /// The maximum number of errors that [`parse_all_with_options`] collects before it
+  /// stops parsing, which is 100 by default.
+  ///
+  /// If this limit is reached, then a final
+  /// [`TooManyErrors`](syntax::ErrorKind::TooManyErrors) error is appended at the
+  /// position of the next error.
+  pub max_errors: usize,
+  /// Whether to reject the unquote abbreviations `,` and `,@` outside of any
+  /// quasiquote with an
+  /// [`UnquoteOutsideQuasiquote`](syntax::ErrorKind::UnquoteOutsideQuasiquote) error.
+  ///
+  /// Only the abbreviations are checked, so the long forms such as `(unquote datum)`
+  /// are neither checked nor counted towards the depth of nesting.
+  pub check_unquote_depth: bool,
+  /// Whether to strip a leading shebang line, such as `#!/usr/bin/env luna`, which is
+  /// enabled by default.
+  ///
+  /// Embedders parsing fragments of source code that may legitimately begin with `#!`
+  /// reader syntax should disable this so that the first line is lexed as usual.
+  pub strip_shebang: bool,
+  /// Whether [`parse_all_with_options`] should recover from a list that is closed by
+  /// the wrong kind of bracket by treating that bracket as its closer anyway.
+  ///
+  /// The [`UnexpectedBracket`](syntax::ErrorKind::UnexpectedBracket) error is still
+  /// reported, but parsing continues after the list rather than skipping the rest of
+  /// its top-level form. The closer that the list was expected to have is then
+  /// skipped if it later appears at the top level, as in `(a ] b)`, which is parsed
+  /// as `(a)` followed by `b` with a single error.
+  pub recover_brackets: bool,
+  /// Whether to permit underscores as digit separators within decimal integer and
+  /// floating-point literals, as in `1_000_000`, which is disabled by default for
+  /// fidelity to R7RS.
+  ///
+  /// See [`LexerOptions::allow_digit_separators`] for the exact rules.
+  pub allow_digit_separators: bool,
+  /// Whether to parse here-doc strings of the form `#<<TAG` into string atoms, whose
+  /// lines are taken verbatim up to a line consisting of exactly `TAG`.
+  ///
+  /// A here-doc without a terminating line is rejected with an
+  /// [`UnterminatedHereDoc`](syntax::ErrorKind::UnterminatedHereDoc) error whose span
+  /// covers the opening `#<<` and its tag. See [`LexerOptions::allow_heredocs`] for the
+  /// exact rules.
+  pub allow_heredocs: bool,
+  /// Whether to put numeric atoms into a canonical form, so that numbers that are
+  /// equal by value are also structurally equal.
+  ///
+  /// Integers are converted to decimal, so that `#xff` is parsed as `255`, and the
+  /// negative floating-point zero and NaN are replaced with their positive
+  /// counterparts, so that `-0.0` is parsed as `0.0` and `-nan.0` as `+nan.0`. Integer
+  /// literals such as `-0` are already parsed as the same value as `0`.
+  pub canonicalize_numbers: bool,
+  /// Whether to read only data rather than code, such that the abbreviations `'`,
+  /// `` ` ``, `,` and `,@` are rejected with an
+  /// [`AbbreviationInData`](syntax::ErrorKind::AbbreviationInData) error instead of
+  /// being expanded into forms such as `(quote datum)`.
+  ///
+  /// This is intended for reading untrusted data, which should consist only of plain
+  /// literals, as with [`parse_data`]. The long forms such as `(quote datum)` are still
+  /// accepted, since they are merely lists.
+  pub data_only: bool,
+}
+
+impl Default for ParseOptions {
+  fn default() -> Self {
+    Self {
+      normalize_nfc: false,
+      forbid_tabs: false,
+      commas_as_whitespace: false,
+      symbol_char: None,
+      braces_as_maps: false,
+      strict_brackets: false,
+      max_bytes: None,
+      max_token_len: None,
+      max_list_len: None,
+      lenient_hash: false,
+      lenient_invalid: false,
+      max_errors: 100,
+      check_unquote_depth: false,
+      strip_shebang: true,
+      recover_brackets: false,
+      allow_digit_separators: false,
+      allow_heredocs: false,
+      canonicalize_numbers: false,
+      data_only: false,
+    }
+  }
+}
+
+impl ParseOptions {
+  /// Strip the byte order mark and, if enabled, the shebang line from an input.
+  ///
+  /// This is the text that is actually parsed, so the spans of any data or errors
+  /// produced by parsing the input with these options are relative to it. Callers
+  /// that slice the input or locate errors within it should do so against this text.
+  pub fn strip<'a>(&self, input: &'a str) -> &'a str {
+    let input = strip_bom(input);
+    if self.strip_shebang {
+      strip_shebang(input)
+    } else {
+      input
+    }
+  }
+
+  /// Strip the byte order mark from an input, returning the rest along with the offset
+  /// within it of the text that is actually parsed with these options.
+  ///
+  /// The returned text is the input as an editor would display it, including any
+  /// shebang line. Since the spans produced by parsing are relative to the text after
+  /// stripping, adding the offset to a span makes it relative to the returned text
+  /// instead, which is what the line and column of an error should be reported
+  /// against.
+  pub fn display_source<'a>(&self, input: &'a str) -> (&'a str, BytePos) {
+    let input = strip_bom(input);
+    (input, input.len() - self.strip(input).len())
+  }
+
+  /// Get the options for the lexer that correspond to these options.
+  fn lexer_options(&self) -> LexerOptions {
+    LexerOptions {
+      forbid_tabs: self.forbid_tabs,
+      commas_as_whitespace: self.commas_as_whitespace,
+      symbol_char: self.symbol_char,
+      emit_comments: false,
+      emit_eof: false,
+      allow_digit_separators: self.allow_digit_separators,
+      allow_heredocs: self.allow_heredocs,
+    }
+  }
 }
 
 /// A specialisation of [`Result`](std::result::Result) for brevity when writing return
@@ -43,189 +645,2454 @@ macro_rules! error {
 }
 
 /// Parse source code into an abstract syntax tree.
+///
+/// A leading byte order mark and shebang line are stripped from the input before it is
+/// parsed, and the spans of any errors are relative to the input after stripping.
 pub fn parse(input: &str) -> Result<Vec<SExpr>> {
-  let mut lexer = Lexer::new(strip_shebang(input)).peekable();
+  parse_with_options(input, &ParseOptions::default())
+}
 
-  let mut program = Vec::new();
-  while let Some(token) = lexer.peek() {
-    use TokenKind::*;
+/// Parse source code into an abstract syntax tree according to the given options.
+///
+/// As with [`parse`], the spans of any errors are relative to the input after its byte
+/// order mark and shebang line have been stripped.
+pub fn parse_with_options(input: &str, options: &ParseOptions) -> Result<Vec<SExpr>> {
+  with_parser(input, options, |parser| parser.parse_program())?
+}
 
-    let sexpr = match token.kind {
-      Symbol => parse_symbol(&mut lexer),
-      String => parse_string(&mut lexer),
-      Int => parse_int(&mut lexer),
-      Bool => parse_bool(&mut lexer),
-      LParen | LBracket | LBrace => parse_list(&mut lexer)?,
-      RParen | RBracket | RBrace => {
-        error!(token.span.start, token.span.end, UnexpectedToken, found = token.kind)
-      },
-      Invalid => {
-        error!(token.span.start, token.span.end, InvalidToken)
-      },
-    };
-    program.push(sexpr);
-  }
+/// Parse data rather than code into an abstract syntax tree.
+///
+/// This is as with [`parse`], except that abbreviations such as `'datum` are rejected
+/// rather than expanded, as with [`ParseOptions::data_only`].
+pub fn parse_data(input: &str) -> Result<Vec<SExpr>> {
+  parse_with_options(input, &ParseOptions { data_only: true, ..ParseOptions::default() })
+}
+
+/// Parse source code into an abstract syntax tree, recovering from syntax errors.
+///
+/// Rather than stopping at the first syntax error, the parser skips to the end of the
+/// top-level form that contains the error and resumes from there. The forms that were
+/// parsed successfully are returned along with every error that was encountered, in
+/// order of appearance.
+pub fn parse_all(input: &str) -> (Vec<SExpr>, Vec<syntax::Error>) {
+  parse_all_with_options(input, &ParseOptions::default())
+}
 
-  Ok(program)
+/// Parse source code into an abstract syntax tree according to the given options,
+/// recovering from syntax errors as with [`parse_all`].
+pub fn parse_all_with_options(
+  input: &str,
+  options: &ParseOptions,
+) -> (Vec<SExpr>, Vec<syntax::Error>) {
+  match with_parser(input, options, |parser| parser.parse_program_recovering()) {
+    Ok(result) => result,
+    Err(error) => (Vec::new(), vec![error]),
+  }
 }
 
-/// Parse a symbol.
-fn parse_symbol(lexer: &mut Peekable<Lexer>) -> SExpr {
-  SExpr::Symbol(lexer.next().unwrap().lexeme.to_string())
+/// Parse source code into an abstract syntax tree, stopping at the first syntax error.
+///
+/// Every top-level form that was parsed before the first error is returned, along with
+/// that error and the position at which parsing stopped, if there was one. This is the
+/// start of the top-level form containing the error, so that the input from there
+/// onwards may be fixed up and parsed again. Unlike [`parse_all`], parsing does not
+/// resume after the error.
+pub fn parse_upto(input: &str) -> (Vec<SExpr>, Option<(syntax::Error, BytePos)>) {
+  match with_parser(input, &ParseOptions::default(), |parser| {
+    parser.parse_program_upto()
+  }) {
+    Ok(result) => result,
+    Err(error) => (Vec::new(), Some((error, 0))),
+  }
 }
 
-/// Parse a string.
-fn parse_string(lexer: &mut Peekable<Lexer>) -> SExpr {
-  SExpr::String(lexer.next().unwrap().lexeme.to_string())
+/// Split source code into the source text of each of its top-level forms, along with
+/// their spans.
+///
+/// The source text of each form is preserved exactly as written, including its
+/// formatting and any comments within it, but comments between forms belong to
+/// neither. As with [`parse`], a leading byte order mark and shebang line are
+/// stripped, and the spans are relative to the input after stripping.
+pub fn split_forms(input: &str) -> Result<Vec<(&str, Span)>> {
+  let options = ParseOptions::default();
+  let input = options.strip(input);
+
+  Parser::new(input, &options)
+    .spanned()
+    .map(|result| result.map(|(_, span)| (&input[span.start..span.end], span)))
+    .collect()
 }
 
-/// Parse an integer.
-fn parse_int(lexer: &mut Peekable<Lexer>) -> SExpr {
-  SExpr::Int(lexer.next().unwrap().lexeme.parse().unwrap())
+/// Parse source code into an abstract syntax tree as with [`parse`], but by building the
+/// tree from the events of [`parse_events`] with an explicit stack rather than by
+/// recursion.
+///
+/// This exists to compare the performance of the two approaches, and so is only
+/// available with the `iterative-parser` feature.
+#[cfg(feature = "iterative-parser")]
+pub fn parse_iterative(input: &str) -> Result<Vec<SExpr>> {
+  /// Wrap a datum in the labels that were defined for it, from innermost to outermost.
+  fn wrap(datum: SExpr, labels: Vec<u32>) -> SExpr {
+    labels
+      .into_iter()
+      .rev()
+      .fold(datum, |datum, label| SExpr::Labelled(label, Box::new(datum)))
+  }
+
+  // NOTE: Each frame holds the elements of a list that is under construction, along
+  //       with its brackets and the labels that were defined for it. The bottom frame
+  //       holds the top-level data.
+  let mut frames = vec![(Vec::new(), BracketKind::Paren, Vec::new())];
+  let mut labels = Vec::new();
+  parse_events(input, &mut |event| match event {
+    Event::StartList(bracket, _) => {
+      frames.push((Vec::new(), bracket, std::mem::take(&mut labels)));
+    },
+    Event::Atom(atom, _) => {
+      let datum = wrap(atom, std::mem::take(&mut labels));
+      frames.last_mut().unwrap().0.push(datum);
+    },
+    Event::EndList(_) => {
+      let (list, bracket, list_labels) = frames.pop().unwrap();
+      frames.last_mut().unwrap().0.push(wrap(SExpr::List(list, bracket), list_labels));
+    },
+    Event::Label(label, _) => labels.push(label),
+  })?;
+
+  Ok(frames.pop().unwrap().0)
 }
 
-/// Parse a boolean.
-fn parse_bool(lexer: &mut Peekable<Lexer>) -> SExpr {
-  let lexeme = lexer.next().unwrap().lexeme;
-  let value = match lexeme {
-    "true" => true,
-    "false" => false,
-    _ => unreachable!(),
+/// Preprocess source code according to the given options and run a function with a
+/// parser over it.
+fn with_parser<T>(
+  input: &str,
+  options: &ParseOptions,
+  f: impl FnOnce(&mut Parser<'_>) -> T,
+) -> Result<T> {
+  let limit = options.max_bytes.map_or(MAX_POS, |limit| limit.min(MAX_POS));
+  if input.len() > limit {
+    error!(limit, input.len(), InputTooLarge, limit = limit);
+  }
+
+  let input = options.strip(input);
+  let input = if options.normalize_nfc {
+    Cow::Owned(input.nfc().collect())
+  } else {
+    Cow::Borrowed(input)
   };
+  let lexer = Lexer::with_options(&input, &options.lexer_options());
+  Ok(f(&mut Parser::with_lexer(lexer, options)))
+}
 
-  SExpr::Bool(value)
+/// Parse source code that consists of exactly one datum into an abstract syntax tree.
+///
+/// If the input contains no data, or more than one, then an
+/// [`ExpectedSingleDatum`](syntax::ErrorKind::ExpectedSingleDatum) error is returned,
+/// whose span is either that of the input or of the second datum respectively.
+pub fn parse_single(input: &str) -> Result<SExpr> {
+  let input = strip_shebang(strip_bom(input));
+  let options = ParseOptions::default();
+  Parser::with_lexer(Lexer::new(input), &options).parse_single(input.len())
 }
 
-/// Parse a list.
-fn parse_list(lexer: &mut Peekable<Lexer>) -> Result<SExpr> {
-  let mut list = Vec::new();
+/// An occurrence of a symbol within source code.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SymbolOccurrence {
+  /// The name of the symbol.
+  pub name: String,
+  /// The span of source code that covers the symbol.
+  pub span: Span,
+  /// Whether the symbol occurs within quoted data rather than code.
+  ///
+  /// A symbol is quoted if it occurs within a `quote` form, or within a `quasiquote`
+  /// form but outside of any `unquote` or `unquote-splicing` forms.
+  pub quoted: bool,
+}
 
-  // NOTE: It is an invariant that an opening bracket be present, so we can consume
-  //       it and unwrap.
-  let opener = lexer.next().expect("an opening bracket should always be present");
-  let Span { start: list_start, end: mut list_end } = opener.span;
+/// Collect every occurrence of a symbol within source code, in order of appearance.
+///
+/// The `quote`, `quasiquote`, `unquote`, and `unquote-splicing` symbols that are
+/// introduced by the expansion of abbreviations such as `'datum` are not included,
+/// since they do not occur within the source code.
+pub fn collect_symbols(input: &str) -> Result<Vec<SymbolOccurrence>> {
+  /// The context in which a datum occurs.
+  #[derive(Copy, Clone, Eq, PartialEq)]
+  enum Context {
+    Code,
+    Quoted,
+    Quasiquoted,
+  }
 
-  while let Some(token) = lexer.peek() {
-    use TokenKind::*;
+  fn go(
+    sexpr: &SExpr,
+    context: Context,
+    spans: &mut impl Iterator<Item = Option<Span>>,
+    symbols: &mut Vec<SymbolOccurrence>,
+  ) {
+    match sexpr {
+      SExpr::Symbol(name) => {
+        // NOTE: The spans were recorded in the same order as a pre-order traversal of
+        //       the tree, so there is always a span for each symbol.
+        if let Some(span) = spans.next().expect("every symbol should have a span") {
+          let quoted = context != Context::Code;
+          symbols.push(SymbolOccurrence { name: name.clone(), span, quoted });
+        }
+      },
+      SExpr::List(list, _) => {
+        let inner = if sexpr.as_quoted().is_some() {
+          Context::Quoted
+        } else if sexpr.as_quasiquoted().is_some() {
+          Context::Quasiquoted
+        } else if context == Context::Quasiquoted
+          && (sexpr.as_unquoted().is_some() || sexpr.as_unquote_spliced().is_some())
+        {
+          Context::Code
+        } else {
+          context
+        };
 
-    list_end = token.span.end;
-    list.push(match token.kind {
-      Symbol => parse_symbol(lexer),
-      String => parse_string(lexer),
-      Int => parse_int(lexer),
-      Bool => parse_bool(lexer),
-      LParen | LBracket | LBrace => parse_list(lexer)?,
-      RParen | RBracket | RBrace => {
-        let closer = opener.kind.closer();
-        if token.kind != closer {
-          error!(
-            list_start,
-            list_end,
-            UnexpectedBracket,
-            expected = closer,
-            found = token.kind,
-          )
+        for (i, sexpr) in list.iter().enumerate() {
+          // The head of an abbreviation is in the outer context.
+          let context = if i == 0 { context } else { inner };
+          go(sexpr, context, spans, symbols);
         }
-        break;
       },
-      Invalid => {
-        error!(token.span.start, token.span.end, InvalidToken)
+      SExpr::Map(entries) => {
+        for (key, value) in entries {
+          go(key, context, spans, symbols);
+          go(value, context, spans, symbols);
+        }
       },
-    });
+      SExpr::Labelled(_, datum) => go(datum, context, spans, symbols),
+      SExpr::Keyword(_)
+      | SExpr::String(_)
+      | SExpr::Int(..)
+      | SExpr::Float(_)
+      | SExpr::Bool(_)
+      | SExpr::Char(_)
+      | SExpr::LabelRef(_)
+      | SExpr::Invalid { .. } => {},
+    }
   }
 
-  // Consume the closing bracket.
-  if lexer.next().is_none() {
-    error!(list_start, list_end, UnmatchedBracket, expected = opener.kind.closer());
+  let options = ParseOptions::default();
+  let mut parser =
+    Parser::with_lexer(Lexer::new(strip_shebang(strip_bom(input))), &options);
+  parser.symbol_spans = Some(Vec::new());
+  let program = parser.parse_program()?;
+
+  let mut spans = parser.symbol_spans.unwrap_or_default().into_iter();
+  let mut symbols = Vec::new();
+  for sexpr in &program {
+    go(sexpr, Context::Code, &mut spans, &mut symbols);
   }
 
-  Ok(SExpr::List(list))
+  Ok(symbols)
 }
 
-// TODO: Move this into a module containing program file abstractions.
-/// Strip the shebang line from a string if one is present.
-pub(crate) fn strip_shebang(input: &str) -> &str {
-  if input.starts_with("#!") {
-    // The byte index of the first character after the shebang line.
-    let i = input.find('\n').map(|i| i + 1).unwrap_or_else(|| input.len());
-    &input[i..]
-  } else {
-    input
-  }
+/// An event that is produced while parsing source code with [`parse_events`].
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+  /// The start of a list delimited by the given kind of bracket, along with the span of
+  /// its opening bracket.
+  ///
+  /// An abbreviation such as `'datum` starts a list with round brackets whose span is
+  /// that of the prefix.
+  StartList(BracketKind, Span),
+  /// An atom along with its span.
+  Atom(SExpr, Span),
+  /// The end of the most recently started list, along with the span of its closing
+  /// bracket.
+  ///
+  /// Since an abbreviation has no closing bracket, the span at which it ends is empty.
+  EndList(Span),
+  /// A datum label definition `#n=` along with its span, which labels the datum whose
+  /// events immediately follow.
+  Label(u32, Span),
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+/// Parse source code into a flat stream of events, passing each to the given function
+/// in order of appearance.
+///
+/// Unlike [`parse`], no tree is built, so the memory that is used grows only with the
+/// depth of nesting rather than with the size of the input. Abbreviations are expanded
+/// as they are by `parse`, and the events already passed to the function before a
+/// syntax error is encountered are not retracted.
+pub fn parse_events(input: &str, f: &mut impl FnMut(Event)) -> Result<()> {
+  with_parser(input, &ParseOptions::default(), |parser| parser.parse_events(f))?
+}
 
-  #[test]
-  fn parse_program() -> Result<()> {
-    parse("(defn fac [n]\n(fac (minus n 1)))\n\n(print (fac 5))")?;
-    Ok(())
+/// A streaming parser that parses one top-level datum at a time.
+///
+/// Unlike [`parse_with_options`], a streaming parser does not apply the
+/// [`normalize_nfc`](ParseOptions::normalize_nfc) and
+/// [`max_bytes`](ParseOptions::max_bytes) options, since they apply to the input as a
+/// whole. Spans are relative to the input after its byte order mark and any shebang
+/// line have been stripped.
+pub struct Parser<'a> {
+  /// The stream of tokens being parsed.
+  lexer: Peekable<Lexer<'a>>,
+  /// The options that control the behaviour of the parser.
+  options: &'a ParseOptions,
+  /// The datum labels that have been defined so far.
+  labels: HashSet<u32>,
+  /// The spans of the symbols that have been parsed so far, if they are being recorded.
+  ///
+  /// Symbols that were introduced by the parser rather than appearing in the source
+  /// code, such as the `quote` in the expansion of `'datum`, have no span.
+  symbol_spans: Option<Vec<Option<Span>>>,
+  /// The position after the last token that was consumed.
+  end: BytePos,
+  /// The number of brackets that have been opened but not yet closed.
+  depth: usize,
+  /// The number of quasiquotes that enclose the current datum, less the number of
+  /// unquotes that enclose it within them.
+  quasiquote_depth: usize,
+  /// The next top-level datum and its span, if it has been parsed ahead of time by
+  /// [`Parser::peek_datum`].
+  lookahead: Option<Result<(SExpr, Span)>>,
+  /// Whether to recover from lists closed by the wrong kind of bracket, which is only
+  /// the case when recovering from errors in general.
+  recover_brackets: bool,
+  /// The errors that have been recovered from within the current top-level datum.
+  recovered: Vec<syntax::Error>,
+  /// The closers that were expected by lists that were recovered from and that have
+  /// yet to be skipped.
+  owed_closers: Vec<TokenKind>,
+}
+
+impl<'a> Parser<'a> {
+  /// Create a new streaming parser over the given input.
+  pub fn new(input: &'a str, options: &'a ParseOptions) -> Self {
+    let lexer = Lexer::with_options(options.strip(input), &options.lexer_options());
+    Self::with_lexer(lexer, options)
   }
 
-  #[test]
-  fn parse_symbol() -> Result<()> {
-    parse("hello")?;
-    parse("foo bar")?;
-    parse("foo\nbar")?;
+  /// Parse the next top-level datum, or return `None` if there are no more.
+  ///
+  /// If the next datum has already been parsed by [`Parser::peek_datum`], then it is
+  /// returned without parsing it again.
+  pub fn next_datum(&mut self) -> Option<Result<SExpr>> {
+    self.next_spanned().map(|result| result.map(|(sexpr, _)| sexpr))
+  }
 
-    Ok(())
+  /// Parse the next top-level datum without consuming it, or return `None` if there
+  /// are no more.
+  ///
+  /// The datum is buffered so that it is returned by the next call to
+  /// [`Parser::next_datum`]. If the next datum contains a syntax error, then this
+  /// returns `None` and the error is returned by the next call to `next_datum`
+  /// instead.
+  pub fn peek_datum(&mut self) -> Option<&SExpr> {
+    if self.lookahead.is_none() {
+      self.lookahead = self.parse_spanned();
+    }
+
+    match &self.lookahead {
+      Some(Ok((sexpr, _))) => Some(sexpr),
+      _ => None,
+    }
   }
 
-  #[test]
-  fn parse_string() -> Result<()> {
-    parse("\"foo\"")?;
-    parse("\"\\\"bar\\\"\"")?;
+  /// Reset this parser to parse a new input from the start, as if it were newly
+  /// created with the same options.
+  ///
+  /// Unlike creating a new parser, this reuses the buffers that the parser has already
+  /// allocated, which avoids reallocating them in hot loops such as a REPL.
+  pub fn reset(&mut self, input: &'a str) {
+    let options = self.options;
+    self.lexer =
+      Lexer::with_options(options.strip(input), &options.lexer_options()).peekable();
+    self.labels.clear();
+    if let Some(spans) = &mut self.symbol_spans {
+      spans.clear();
+    }
+    self.end = 0;
+    self.depth = 0;
+    self.quasiquote_depth = 0;
+    self.lookahead = None;
+    self.recover_brackets = false;
+    self.recovered.clear();
+    self.owed_closers.clear();
+  }
 
-    Ok(())
+  /// Turn this parser into an iterator over the remaining top-level data and their
+  /// spans.
+  pub fn spanned(self) -> ParserIter<'a> {
+    ParserIter { parser: self, failed: false }
   }
 
-  #[test]
-  fn parse_int() -> Result<()> {
-    parse("10")?;
-    parse("0 11")?;
-    parse("0 -11")?;
+  /// Create a new parser over the tokens of a given lexer.
+  fn with_lexer(lexer: Lexer<'a>, options: &'a ParseOptions) -> Self {
+    Self {
+      lexer: lexer.peekable(),
+      options,
+      labels: HashSet::new(),
+      symbol_spans: None,
+      end: 0,
+      depth: 0,
+      quasiquote_depth: 0,
+      lookahead: None,
+      recover_brackets: false,
+      recovered: Vec::new(),
+      owed_closers: Vec::new(),
+    }
+  }
 
-    Ok(())
+  /// Consume the next token.
+  fn bump(&mut self) -> Option<Token<'a>> {
+    let token = self.lexer.next()?;
+    self.end = token.span.end;
+    if token.kind.is_opener() {
+      self.depth += 1;
+    } else if token.kind.is_closer() {
+      self.depth = self.depth.saturating_sub(1);
+    }
+
+    Some(token)
   }
 
-  #[test]
-  fn parse_bool() -> Result<()> {
-    parse("true")?;
-    parse("false")?;
+  /// Get the next top-level datum along with its span, or return `None` if there are
+  /// no more.
+  fn next_spanned(&mut self) -> Option<Result<(SExpr, Span)>> {
+    self.lookahead.take().or_else(|| self.parse_spanned())
+  }
 
-    Ok(())
+  /// Parse the next top-level datum along with its span, ignoring any lookahead, or
+  /// return `None` if there are no more.
+  fn parse_spanned(&mut self) -> Option<Result<(SExpr, Span)>> {
+    if let Err(error) = self.skip_datum_comments() {
+      return Some(Err(error));
+    }
+
+    let start = self.lexer.peek()?.span.start;
+    Some(self.parse_datum().map(|sexpr| (sexpr, Span { start, end: self.end })))
   }
 
-  #[test]
-  fn parse_list() -> Result<()> {
-    // Can we parse empty lists?
-    parse("()")?;
-    parse("[]")?;
-    parse("{}")?;
+  /// Parse every datum in the token stream.
+  fn parse_program(&mut self) -> Result<Vec<SExpr>> {
+    let mut program = Vec::new();
+    loop {
+      self.skip_datum_comments()?;
+      if self.lexer.peek().is_none() {
+        break;
+      }
+      program.push(self.parse_datum()?);
+    }
 
-    // Can we parse normal lists?
-    parse("(1 2 3)")?;
-    parse("[1 2 3]")?;
-    parse("{1 2 3}")?;
+    Ok(program)
+  }
 
-    // Can we parse nested lists?
-    parse("(1 [2 {3}])")?;
-    parse("{1 [2 3]}")?;
+  /// Parse every datum in the token stream into a stream of events.
+  fn parse_events(&mut self, f: &mut impl FnMut(Event)) -> Result<()> {
+    use TokenKind::*;
 
-    Ok(())
+    /// A form that has been started but not yet finished, along with the token that
+    /// started it.
+    enum Frame<'a> {
+      List(Token<'a>),
+      Abbreviation(Token<'a>),
+      Label(Token<'a>),
+    }
+
+    let mut frames = Vec::new();
+    loop {
+      self.skip_datum_comments()?;
+      let Some(&token) = self.lexer.peek() else { break };
+
+      let finished = match (token.kind, frames.last()) {
+        (kind, _) if kind.is_opener() => {
+          self.bump();
+          f(Event::StartList(token.kind.bracket_kind(), token.span));
+          frames.push(Frame::List(token));
+          false
+        },
+        (kind, Some(&Frame::List(opener))) if kind.is_closer() => {
+          let closer = opener.kind.closer();
+          if token.kind != closer {
+            error!(
+              token.span.start,
+              token.span.end,
+              UnexpectedBracket,
+              expected = closer,
+              found = token.kind,
+              opener = opener.span,
+            )
+          }
+
+          self.bump();
+          frames.pop();
+          f(Event::EndList(token.span));
+          true
+        },
+        (Quote | Quasiquote | Unquote | UnquoteSplicing, _)
+          if self.options.data_only =>
+        {
+          error!(
+            token.span.start,
+            token.span.end,
+            AbbreviationInData,
+            found = token.kind
+          )
+        },
+        (Quote | Quasiquote | Unquote | UnquoteSplicing, _) => {
+          self.bump();
+          let keyword = SExpr::Symbol(abbreviation_keyword(token.kind).to_string());
+          f(Event::StartList(BracketKind::Paren, token.span));
+          f(Event::Atom(keyword, token.span));
+          frames.push(Frame::Abbreviation(token));
+          false
+        },
+        (LabelDef, _) => {
+          self.bump();
+          let label = parse_label(token)?;
+          self.labels.insert(label);
+          f(Event::Label(label, token.span));
+          frames.push(Frame::Label(token));
+          false
+        },
+        _ => {
+          let atom = self.parse_datum()?;
+          f(Event::Atom(atom, Span { start: token.span.start, end: self.end }));
+          true
+        },
+      };
+
+      // NOTE: Finishing a datum also finishes every abbreviation and label definition
+      //       that immediately encloses it.
+      if finished {
+        while let Some(Frame::Abbreviation(_) | Frame::Label(_)) = frames.last() {
+          if let Some(Frame::Abbreviation(_)) = frames.pop() {
+            f(Event::EndList(Span { start: self.end, end: self.end }));
+          }
+        }
+      }
+    }
+
+    match frames.last() {
+      Some(Frame::List(opener)) => {
+        error!(
+          opener.span.start,
+          self.end,
+          UnmatchedBracket,
+          expected = opener.kind.closer()
+        )
+      },
+      Some(Frame::Abbreviation(prefix) | Frame::Label(prefix)) => {
+        error!(prefix.span.start, prefix.span.end, UnexpectedEof, expected = "a datum")
+      },
+      None => Ok(()),
+    }
   }
 
-  #[test]
-  fn ignore_shebang() {
-    const PROGRAM1: &str = "#!/usr/bin/env luna\n(define x 10)\n";
-    assert_eq!(strip_shebang(PROGRAM1), "(define x 10)\n");
+  /// Parse every datum in the token stream up to the first syntax error, returning the
+  /// error along with the start of the top-level form in which it occurred.
+  fn parse_program_upto(&mut self) -> (Vec<SExpr>, Option<(syntax::Error, BytePos)>) {
+    let mut program = Vec::new();
+    loop {
+      let start = self.lexer.peek().map_or(self.end, |token| token.span.start);
+      match self.parse_spanned() {
+        Some(Ok((sexpr, _))) => program.push(sexpr),
+        Some(Err(error)) => return (program, Some((error, start))),
+        None => return (program, None),
+      }
+    }
+  }
 
-    const PROGRAM2: &str = "#!/usr/bin/env luna";
-    assert!(strip_shebang(PROGRAM2).is_empty());
+  /// Parse every datum in the token stream, recovering from any syntax errors by
+  /// skipping to the end of the top-level form in which they occur.
+  fn parse_program_recovering(&mut self) -> (Vec<SExpr>, Vec<syntax::Error>) {
+    self.recover_brackets = self.options.recover_brackets;
 
-    const PROGRAM3: &str = "#!/usr/bin/env luna\n";
-    assert!(strip_shebang(PROGRAM3).is_empty());
+    let limit = self.options.max_errors;
+    let mut program = Vec::new();
+    let mut errors = Vec::new();
+    loop {
+      let start = self.lexer.peek().map(|token| token.span.start);
+      let result = self.skip_datum_comments().and_then(|()| {
+        while self.skip_owed_closer() {
+          self.skip_datum_comments()?;
+        }
+        match self.lexer.peek() {
+          Some(_) => self.parse_datum().map(Some),
+          None => Ok(None),
+        }
+      });
+      // NOTE: The errors that were recovered from count towards the limit too.
+      let recovered = std::mem::take(&mut self.recovered);
+      if !recovered.into_iter().all(|error| push_error(&mut errors, error, limit)) {
+        break;
+      }
+
+      match result {
+        Ok(Some(sexpr)) => program.push(sexpr),
+        Ok(None) => break,
+        Err(mut error) => {
+          if error.kind == syntax::ErrorKind::InvalidToken {
+            error.span.end = self.skip_invalid_run(error.span.start).max(error.span.end);
+          }
+          if !push_error(&mut errors, error, limit) {
+            break;
+          }
+          // NOTE: The offending token is skipped if it has not been consumed, so that
+          //       parsing always makes progress.
+          if self.lexer.peek().map(|token| token.span.start) == start {
+            self.bump();
+          }
+          while self.depth > 0 && self.bump().is_some() {}
+        },
+      }
+    }
+
+    (program, errors)
+  }
+
+  /// Skip the run of adjacent invalid tokens that starts at the given position, if
+  /// any, returning the position at which the run ends.
+  ///
+  /// This allows a run of invalid characters such as `\\\` to be reported as a single
+  /// error rather than as one error per character.
+  fn skip_invalid_run(&mut self, start: BytePos) -> BytePos {
+    let mut end = start;
+    while let Some(&token) = self.lexer.peek() {
+      if token.kind != TokenKind::Invalid || token.span.start != end {
+        break;
+      }
+      end = token.span.end;
+      self.bump();
+    }
+
+    end
+  }
+
+  /// Skip the next token if it is the closer that was expected by the most recent list
+  /// that was recovered from, returning whether it was skipped.
+  fn skip_owed_closer(&mut self) -> bool {
+    let Some(&owed) = self.owed_closers.last() else { return false };
+    if self.lexer.peek().map(|token| token.kind) != Some(owed) {
+      return false;
+    }
+
+    self.owed_closers.pop();
+    self.bump();
+    true
+  }
+
+  /// Parse exactly one datum from the token stream of an input of the given length.
+  fn parse_single(&mut self, len: BytePos) -> Result<SExpr> {
+    self.skip_datum_comments()?;
+    if self.lexer.peek().is_none() {
+      error!(0, len, ExpectedSingleDatum);
+    }
+    let sexpr = self.parse_datum()?;
+
+    self.skip_datum_comments()?;
+    if let Some(&token) = self.lexer.peek() {
+      self.parse_datum()?;
+      error!(token.span.start, self.end, ExpectedSingleDatum);
+    }
+
+    Ok(sexpr)
+  }
+
+  /// Parse a single datum.
+  fn parse_datum(&mut self) -> Result<SExpr> {
+    use TokenKind::*;
+
+    // NOTE: It is an invariant that a token be present, so we can unwrap.
+    let token = self.lexer.peek().expect("a token should always be present");
+    if let Some(limit) = self.options.max_token_len {
+      if token.lexeme.len() > limit {
+        // Back up to the nearest character boundary so that the span remains valid.
+        let mut cut = limit;
+        while !token.lexeme.is_char_boundary(cut) {
+          cut -= 1;
+        }
+        error!(token.span.start + cut, token.span.end, TokenTooLong, limit = limit);
+      }
+    }
+
+    let sexpr = match token.kind {
+      LBrace if self.options.braces_as_maps => self.parse_map()?,
+      LBrace if self.options.strict_brackets => {
+        error!(token.span.start, token.span.end, ReservedBracket, found = token.kind)
+      },
+      kind if kind.is_atom() => self.parse_atom()?,
+      LParen | LBracket | LBrace => self.parse_list()?,
+      Quote | Quasiquote | Unquote | UnquoteSplicing => self.parse_abbreviation()?,
+      LabelDef => self.parse_label_def()?,
+      LabelRef => self.parse_label_ref()?,
+      DatumComment => unreachable!("datum comments should be skipped before a datum"),
+      Comment => unreachable!("comments should only be emitted when requested"),
+      // NOTE: Since dotted pairs are not yet supported, a dot is never valid.
+      Dot => error!(token.span.start, token.span.end, UnexpectedDot),
+      RParen | RBracket | RBrace | Eof => {
+        error!(token.span.start, token.span.end, UnexpectedToken, found = token.kind)
+      },
+      UnsupportedReaderSyntax if self.options.lenient_hash => self.parse_symbol(),
+      UnsupportedReaderSyntax if token.lexeme == "#" => {
+        error!(token.span.start, token.span.end, EmptySymbol)
+      },
+      UnsupportedReaderSyntax if is_stray_hash_symbol(token.lexeme) => {
+        error!(token.span.start, token.span.end, ReservedSymbolStart, ch = '#')
+      },
+      UnsupportedReaderSyntax => {
+        error!(
+          token.span.start,
+          token.span.end,
+          UnsupportedReaderSyntax,
+          form = token.lexeme.to_string(),
+        )
+      },
+      Tab => {
+        error!(token.span.start, token.span.end, TabInSource)
+      },
+      Invalid if self.options.lenient_invalid => {
+        let token = self.bump().unwrap();
+        SExpr::Invalid { text: token.lexeme.to_string(), span: token.span }
+      },
+      Invalid => {
+        error!(token.span.start, token.span.end, InvalidToken)
+      },
+      _ => unreachable!("every remaining kind of token should be an atom"),
+    };
+
+    Ok(sexpr)
+  }
+
+  /// Parse an atom, which is a datum that consists of a single token.
+  fn parse_atom(&mut self) -> Result<SExpr> {
+    use TokenKind::*;
+
+    let token = self.lexer.peek().expect("a token should always be present");
+    let sexpr = match token.kind {
+      Symbol => self.parse_symbol(),
+      PipeSymbol => self.parse_pipe_symbol()?,
+      Keyword => self.parse_keyword(),
+      String => self.parse_string()?,
+      HereDoc => self.parse_heredoc()?,
+      Int => self.parse_int()?,
+      Float => self.parse_float(),
+      Bool => self.parse_bool(),
+      Char => self.parse_char()?,
+      kind => unreachable!("{kind:?} is not an atom"),
+    };
+
+    Ok(sexpr)
+  }
+
+  /// Parse a symbol.
+  fn parse_symbol(&mut self) -> SExpr {
+    let token = self.bump().unwrap();
+    if let Some(spans) = &mut self.symbol_spans {
+      spans.push(Some(token.span));
+    }
+
+    SExpr::Symbol(token.lexeme.to_string())
+  }
+
+  /// Parse a symbol written between pipes.
+  fn parse_pipe_symbol(&mut self) -> Result<SExpr> {
+    let token = self.bump().unwrap();
+    if let Some(spans) = &mut self.symbol_spans {
+      spans.push(Some(token.span));
+    }
+    // Strip the surrounding pipes.
+    let raw = &token.lexeme[1..token.lexeme.len() - 1];
+
+    SExpr::try_symbol(raw).map_err(|error| shift_error(error, token.span.start + 1))
+  }
+
+  /// Parse a keyword.
+  fn parse_keyword(&mut self) -> SExpr {
+    SExpr::Keyword(self.bump().unwrap().lexeme[1..].to_string())
+  }
+
+  /// Parse a string.
+  fn parse_string(&mut self) -> Result<SExpr> {
+    let token = self.bump().unwrap();
+    // Strip the surrounding quotes.
+    let raw = &token.lexeme[1..token.lexeme.len() - 1];
+
+    SExpr::try_string(raw).map_err(|error| shift_error(error, token.span.start + 1))
+  }
+
+  /// Parse a here-doc string.
+  fn parse_heredoc(&mut self) -> Result<SExpr> {
+    let token = self.bump().unwrap();
+    SExpr::try_heredoc(token.lexeme)
+      .map_err(|error| shift_error(error, token.span.start))
+  }
+
+  /// Parse an integer.
+  fn parse_int(&mut self) -> Result<SExpr> {
+    let token = self.bump().unwrap();
+    // NOTE: The special floating-point literals such as `+inf.0` must never reach here,
+    //       since they are only ever lexed as floats.
+    debug_assert_eq!(token.kind, TokenKind::Int, "expected an integer literal");
+    let sexpr = match strip_digit_separators(token.lexeme) {
+      Cow::Borrowed(lexeme) => {
+        SExpr::try_int(lexeme).map_err(|error| shift_error(error, token.span.start))
+      },
+      // NOTE: Since the span of any error would be relative to the stripped lexeme, it
+      //       is replaced with that of the whole token.
+      Cow::Owned(lexeme) => SExpr::try_int(&lexeme)
+        .map_err(|error| syntax::Error { span: token.span, ..error }),
+    }?;
+
+    match sexpr {
+      SExpr::Int(value, _) if self.options.canonicalize_numbers => {
+        Ok(SExpr::Int(value, Radix::Decimal))
+      },
+      sexpr => Ok(sexpr),
+    }
+  }
+
+  /// Parse a floating-point number.
+  fn parse_float(&mut self) -> SExpr {
+    let token = self.bump().unwrap();
+    debug_assert_eq!(token.kind, TokenKind::Float, "expected a floating-point literal");
+
+    let value = match token.lexeme {
+      "+inf.0" => f64::INFINITY,
+      "-inf.0" => f64::NEG_INFINITY,
+      "+nan.0" => f64::NAN,
+      "-nan.0" => -f64::NAN,
+      lexeme => strip_digit_separators(lexeme).parse().unwrap(),
+    };
+
+    if !self.options.canonicalize_numbers {
+      SExpr::Float(value)
+    } else if value.is_nan() {
+      SExpr::Float(f64::NAN)
+    } else if value == 0.0 {
+      SExpr::Float(0.0)
+    } else {
+      SExpr::Float(value)
+    }
+  }
+
+  /// Parse a boolean.
+  fn parse_bool(&mut self) -> SExpr {
+    let lexeme = self.bump().unwrap().lexeme;
+    let value = match lexeme {
+      "#t" | "#true" => true,
+      "#f" | "#false" => false,
+      _ => unreachable!(),
+    };
+
+    SExpr::Bool(value)
+  }
+
+  /// Parse a character.
+  fn parse_char(&mut self) -> Result<SExpr> {
+    let token = self.bump().unwrap();
+    SExpr::try_char(token.lexeme).map_err(|error| shift_error(error, token.span.start))
+  }
+
+  /// Parse a list.
+  fn parse_list(&mut self) -> Result<SExpr> {
+    let (list, bracket, _) = self.parse_elements()?;
+    Ok(SExpr::List(list, bracket))
+  }
+
+  /// Parse a map of the form `{key value ...}`.
+  fn parse_map(&mut self) -> Result<SExpr> {
+    let (elements, _, span) = self.parse_elements()?;
+    if elements.len() % 2 != 0 {
+      error!(span.start, span.end, OddMapEntries);
+    }
+
+    let mut elements = elements.into_iter();
+    let mut entries = Vec::new();
+    while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+      entries.push((key, value));
+    }
+
+    Ok(SExpr::Map(entries))
+  }
+
+  /// Parse the elements of a bracketed sequence, returning them along with the kind of
+  /// bracket that delimited them and the span of the whole sequence.
+  fn parse_elements(&mut self) -> Result<(Vec<SExpr>, BracketKind, Span)> {
+    let mut elements = Vec::new();
+
+    // NOTE: It is an invariant that an opening bracket be present, so we can consume
+    //       it and unwrap.
+    let opener = self.bump().expect("an opening bracket should always be present");
+    let Span { start: list_start, end: mut list_end } = opener.span;
+
+    loop {
+      self.skip_datum_comments()?;
+      let Some(token) = self.lexer.peek() else { break };
+
+      list_end = token.span.end;
+      if token.kind.is_closer() {
+        let closer = opener.kind.closer();
+        if token.kind != closer {
+          if !self.recover_brackets {
+            error!(
+              token.span.start,
+              token.span.end,
+              UnexpectedBracket,
+              expected = closer,
+              found = token.kind,
+              opener = opener.span,
+            )
+          }
+
+          // Record the error and treat the wrong closer as the closer of this list.
+          let kind = syntax::ErrorKind::UnexpectedBracket {
+            expected: closer,
+            found: token.kind,
+            opener: opener.span,
+          };
+          self.recovered.push(syntax::Error { span: token.span, kind });
+          self.owed_closers.push(closer);
+        }
+        break;
+      }
+
+      if let Some(limit) = self.options.max_list_len {
+        if elements.len() >= limit {
+          error!(token.span.start, token.span.end, ListTooLong, limit = limit);
+        }
+      }
+
+      elements.push(self.parse_datum()?);
+    }
+
+    // Consume the closing bracket.
+    let Some(closer) = self.bump() else {
+      error!(list_start, list_end, UnmatchedBracket, expected = opener.kind.closer());
+    };
+
+    let span = Span { start: list_start, end: closer.span.end };
+    Ok((elements, opener.kind.bracket_kind(), span))
+  }
+
+  /// Parse an abbreviation such as `'datum` into its expanded form `(quote datum)`.
+  fn parse_abbreviation(&mut self) -> Result<SExpr> {
+    use TokenKind::*;
+
+    let prefix = self.bump().expect("an abbreviation prefix should always be present");
+    let keyword = abbreviation_keyword(prefix.kind);
+    if self.options.data_only {
+      error!(prefix.span.start, prefix.span.end, AbbreviationInData, found = prefix.kind)
+    }
+
+    if let Some(spans) = &mut self.symbol_spans {
+      spans.push(None);
+    }
+
+    let outer_depth = self.quasiquote_depth;
+    match prefix.kind {
+      Quasiquote => self.quasiquote_depth += 1,
+      Unquote | UnquoteSplicing
+        if self.options.check_unquote_depth && outer_depth == 0 =>
+      {
+        error!(
+          prefix.span.start,
+          prefix.span.end,
+          UnquoteOutsideQuasiquote,
+          found = prefix.kind,
+        )
+      },
+      Unquote | UnquoteSplicing => self.quasiquote_depth = outer_depth.saturating_sub(1),
+      _ => {},
+    }
+    // A prefix must be followed by the datum that it abbreviates.
+    let datum = self.expect_datum(prefix);
+    self.quasiquote_depth = outer_depth;
+    let datum = datum?;
+
+    Ok(SExpr::List(vec![SExpr::Symbol(keyword.to_string()), datum], BracketKind::Paren))
+  }
+
+  /// Parse a datum label definition of the form `#n=datum`.
+  fn parse_label_def(&mut self) -> Result<SExpr> {
+    let token = self.bump().expect("a label definition should always be present");
+    let label = parse_label(token)?;
+
+    // NOTE: The label is defined before its datum is parsed so that the datum may
+    //       refer to itself, which is how circular structure is written.
+    self.labels.insert(label);
+
+    // A label definition must be followed by the datum that it labels.
+    let datum = self.expect_datum(token)?;
+
+    Ok(SExpr::Labelled(label, Box::new(datum)))
+  }
+
+  /// Skip any datum comments of the form `#;datum` at the current position.
+  fn skip_datum_comments(&mut self) -> Result<()> {
+    while let Some(&token) = self.lexer.peek() {
+      if token.kind != TokenKind::DatumComment {
+        break;
+      }
+      self.bump();
+
+      // NOTE: Any symbols within the commented datum do not appear in the final tree,
+      //       so their spans must be discarded too.
+      let recorded = self.symbol_spans.as_ref().map(Vec::len);
+      self.expect_datum(token)?;
+      if let (Some(spans), Some(len)) = (&mut self.symbol_spans, recorded) {
+        spans.truncate(len);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Parse the datum that must follow a prefix token such as `'` or `#;`.
+  fn expect_datum(&mut self, prefix: Token) -> Result<SExpr> {
+    self.skip_datum_comments()?;
+    if self.lexer.peek().is_none() {
+      error!(prefix.span.start, prefix.span.end, UnexpectedEof, expected = "a datum");
+    }
+
+    self.parse_datum()
+  }
+
+  /// Parse a datum label reference of the form `#n#`.
+  fn parse_label_ref(&mut self) -> Result<SExpr> {
+    let token = self.bump().expect("a label reference should always be present");
+    let label = parse_label(token)?;
+
+    if !self.labels.contains(&label) {
+      error!(token.span.start, token.span.end, UndefinedLabel, label = label);
+    }
+
+    Ok(SExpr::LabelRef(label))
+  }
+}
+
+/// Translate an error from a sub-parse of the slice of input at the given offset into
+/// the coordinate space of the whole input.
+fn shift_error(error: syntax::Error, offset: BytePos) -> syntax::Error {
+  syntax::Error { span: error.span.offset(offset), ..error }
+}
+
+/// Record an error, unless the given number of errors has already been recorded, in
+/// which case a [`TooManyErrors`](syntax::ErrorKind::TooManyErrors) error is recorded
+/// at its position instead.
+///
+/// This returns whether parsing should continue.
+fn push_error(
+  errors: &mut Vec<syntax::Error>,
+  error: syntax::Error,
+  limit: usize,
+) -> bool {
+  if errors.len() >= limit {
+    let kind = syntax::ErrorKind::TooManyErrors { limit };
+    errors.push(syntax::Error { kind, ..error });
+    return false;
+  }
+
+  errors.push(error);
+  true
+}
+
+/// An iterator over the top-level data of a [`Parser`] and their spans.
+///
+/// The iterator ends after yielding the first syntax error that it encounters.
+pub struct ParserIter<'a> {
+  /// The underlying parser.
+  parser: Parser<'a>,
+  /// Whether a syntax error has been yielded.
+  failed: bool,
+}
+
+impl Iterator for ParserIter<'_> {
+  type Item = Result<(SExpr, Span)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.failed {
+      return None;
+    }
+
+    let result = self.parser.next_spanned()?;
+    self.failed = result.is_err();
+    Some(result)
+  }
+}
+
+/// Strip any digit separators from the lexeme of a number.
+fn strip_digit_separators(lexeme: &str) -> Cow<'_, str> {
+  if lexeme.contains('_') {
+    Cow::Owned(lexeme.replace('_', ""))
+  } else {
+    Cow::Borrowed(lexeme)
+  }
+}
+
+/// Check whether the lexeme of an unsupported `#`-prefixed form looks like a symbol
+/// with a stray leading `#`, such as `#foo`, rather than like reader syntax reserved by
+/// R7RS, such as vectors, directives, block comments, and numeric prefixes.
+fn is_stray_hash_symbol(lexeme: &str) -> bool {
+  let rest = &lexeme[1..];
+  !rest.ends_with('(')
+    && rest.starts_with(|c: char| {
+      (c.is_alphabetic() && !"bBdDeEiIoOxX".contains(c)) || "$%*+-./:<>?@^_~".contains(c)
+    })
+}
+
+/// Get the keyword that an abbreviation prefix such as `'` expands to.
+fn abbreviation_keyword(prefix: TokenKind) -> &'static str {
+  match prefix {
+    TokenKind::Quote => "quote",
+    TokenKind::Quasiquote => "quasiquote",
+    TokenKind::Unquote => "unquote",
+    TokenKind::UnquoteSplicing => "unquote-splicing",
+    _ => unreachable!("only an abbreviation prefix has a keyword"),
+  }
+}
+
+/// Parse the number out of a datum label token such as `#1=` or `#1#`.
+fn parse_label(token: Token) -> Result<u32> {
+  // Strip the leading `#` and the trailing `=` or `#`.
+  let digits = &token.lexeme[1..token.lexeme.len() - 1];
+  match digits.parse() {
+    Ok(label) => Ok(label),
+    Err(_) => error!(token.span.start, token.span.end, InvalidToken),
+  }
+}
+
+// TODO: Move this into a module containing program file abstractions.
+/// Strip the UTF-8 byte order mark from a string if one is present.
+pub(crate) fn strip_bom(input: &str) -> &str {
+  input.strip_prefix('\u{FEFF}').unwrap_or(input)
+}
+
+// TODO: Move this into a module containing program file abstractions.
+/// Strip the shebang line from a string if one is present.
+pub(crate) fn strip_shebang(input: &str) -> &str {
+  if input.starts_with("#!") {
+    // The byte index of the first character after the shebang line.
+    let i = input.find('\n').map(|i| i + 1).unwrap_or_else(|| input.len());
+    &input[i..]
+  } else {
+    input
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_program() -> Result<()> {
+    parse("(defn fac [n]\n(fac (minus n 1)))\n\n(print (fac 5))")?;
+    Ok(())
+  }
+
+  #[test]
+  fn parse_single() -> Result<()> {
+    assert_eq!(super::parse_single(" (a b) ; comment")?, parse("(a b)")?[0]);
+    assert_eq!(super::parse_single("#;a b")?, SExpr::Symbol("b".to_string()));
+
+    assert!(super::parse_single("").is_err());
+    let error = super::parse_single(" ; comment").unwrap_err();
+    assert_eq!(error.span, Span { start: 0, end: 10 });
+    assert_eq!(error.kind, syntax::ErrorKind::ExpectedSingleDatum);
+
+    let error = super::parse_single("a (b c) d").unwrap_err();
+    assert_eq!(error.span, Span { start: 2, end: 7 });
+    assert_eq!(error.kind, syntax::ErrorKind::ExpectedSingleDatum);
+
+    Ok(())
+  }
+
+  #[test]
+  fn display_bool_style() -> Result<()> {
+    let tree = parse("(#true #f)")?;
+    assert_eq!(tree[0].to_string(), "(#t #f)");
+
+    let config =
+      DisplayConfig { bool_style: BoolStyle::Long, ..DisplayConfig::default() };
+    assert_eq!(tree[0].display_with(&config).to_string(), "(#true #false)");
+
+    Ok(())
+  }
+
+  #[test]
+  fn display_symbol_style() -> Result<()> {
+    assert_eq!(SExpr::from("foo").to_string(), "foo");
+    assert_eq!(SExpr::from("hello world").to_string(), "|hello world|");
+    assert_eq!(SExpr::from("a|b\\c").to_string(), r"|a\|b\\c|");
+    assert_eq!(SExpr::from("tab\there").to_string(), r"|tab\there|");
+    // Symbols that would be read back as other tokens should be quoted too.
+    for name in ["", "42", "+inf.0", ".", "#t", ":key", "(a)", "a;b"] {
+      assert_eq!(SExpr::from(name).to_string(), format!("|{name}|"));
+    }
+    assert_eq!(parse("(+ - ... λ set! .foo)")?[0].to_string(), "(+ - ... λ set! .foo)");
+
+    let config =
+      DisplayConfig { symbol_style: SymbolStyle::Bare, ..DisplayConfig::default() };
+    let sexpr = SExpr::from("hello world");
+    assert_eq!(sexpr.display_with(&config).to_string(), "hello world");
+
+    Ok(())
+  }
+
+  #[test]
+  fn convert_into_sexpr() -> Result<()> {
+    assert_eq!(SExpr::from(42), SExpr::Int(42, Radix::Decimal));
+    assert_eq!(SExpr::try_from(-7i64), Ok(SExpr::Int(-7, Radix::Decimal)));
+    assert!(SExpr::try_from(i64::MAX).is_err());
+    assert_eq!(SExpr::from(true), SExpr::Bool(true));
+    assert_eq!(SExpr::from("foo"), SExpr::Symbol("foo".to_string()));
+    assert_eq!(SExpr::from("foo".to_string()), SExpr::String("foo".to_string()));
+
+    let list: SExpr =
+      vec![SExpr::from("f"), SExpr::from(1), SExpr::from(false)].into_iter().collect();
+    assert_eq!(parse("(f 1 #f)")?, [list]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_symbol() -> Result<()> {
+    parse("hello")?;
+    parse("foo bar")?;
+    parse("foo\nbar")?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_pipe_symbol() -> Result<()> {
+    let symbol = |name: &str| SExpr::Symbol(name.to_string());
+    assert_eq!(parse("|hello world|")?, [symbol("hello world")]);
+    assert_eq!(parse("|foo|")?, parse("foo")?);
+    assert_eq!(parse(r"|a\|b\\c|")?, [symbol("a|b\\c")]);
+    assert_eq!(parse("|a\nb|")?, [symbol("a\nb")]);
+    assert_eq!(parse(r"|\x3bb;\t|")?, [symbol("λ\t")]);
+
+    // Every symbol should round-trip through its display form.
+    for name in ["a|b", "a\nb", "λ\u{7}", "|", "", "a\\", "\"quoted\"", "(a b)"] {
+      let displayed = symbol(name).to_string();
+      assert_eq!(parse(&displayed)?, [symbol(name)], "{displayed}");
+    }
+    assert_eq!(symbol("a\nb\u{7}").to_string(), r"|a\nb\x7;|");
+
+    let error = parse(r"(f |a\qb|)").unwrap_err();
+    assert_eq!(error.span, Span { start: 5, end: 7 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::InvalidEscape { sequence: r"\q".to_string() }
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_keyword() -> Result<()> {
+    assert_eq!(parse(":foo")?, [SExpr::Keyword("foo".to_string())]);
+    assert_eq!(parse("a:b")?, [SExpr::Symbol("a:b".to_string())]);
+    assert_eq!(parse(":foo")?[0].to_string(), ":foo");
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_string() -> Result<()> {
+    assert_eq!(parse("\"foo\"")?, [SExpr::String("foo".to_string())]);
+    assert_eq!(parse("\"\\\"bar\\\"\"")?, [SExpr::String("\"bar\"".to_string())]);
+    assert_eq!(parse("\"a\\nb\"")?[0].to_string(), "\"a\\nb\"");
+
+    let error = parse("(f \"a\\qb\")").unwrap_err();
+    assert_eq!(error.span, Span { start: 5, end: 7 });
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_heredoc() -> Result<()> {
+    let options = ParseOptions { allow_heredocs: true, ..ParseOptions::default() };
+
+    let program = parse_with_options("(f #<<EOF\n  \\n \"x\"\n\nEOF\n 1)", &options)?;
+    assert_eq!(
+      program,
+      [SExpr::from_iter([
+        SExpr::from("f"),
+        SExpr::String("  \\n \"x\"\n".to_string()),
+        SExpr::from(1)
+      ])],
+    );
+
+    let error = parse_with_options("(f #<<EOF\nabc\nEOF)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 9 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnterminatedHereDoc { tag: "EOF".to_string() }
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_int() -> Result<()> {
+    parse("10")?;
+    parse("0 11")?;
+    parse("0 -11")?;
+
+    // Integers should retain the radix in which they were written.
+    assert_eq!(parse("#xff")?, [SExpr::Int(255, Radix::Hexadecimal)]);
+    assert_eq!(parse("#xff")?[0].to_string(), "#xff");
+    assert_eq!(parse("#b-110")?[0].to_string(), "#b-110");
+    assert_eq!(parse("#o777")?[0].to_string(), "#o777");
+    assert_eq!(parse("255")?[0].to_string(), "255");
+    assert_eq!(parse("#d255")?[0].to_string(), "255");
+
+    let error = parse("(f 99999999999)").unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 14 });
+    assert_eq!(error.kind, syntax::ErrorKind::IntegerOverflow);
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_float() -> Result<()> {
+    assert_eq!(
+      parse("0.1 -2.5 1e3")?,
+      [SExpr::Float(0.1), SExpr::Float(-2.5), SExpr::Float(1e3)]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn canonicalize_numbers() -> Result<()> {
+    let options = ParseOptions { canonicalize_numbers: true, ..ParseOptions::default() };
+    let parse_canonical = |input| parse_with_options(input, &options);
+
+    assert_eq!(parse_canonical("#xff")?, [SExpr::Int(255, Radix::Decimal)]);
+    assert_eq!(parse_canonical("#b-110")?[0].to_string(), "-6");
+    assert_eq!(parse_canonical("-0")?, parse_canonical("0")?);
+
+    let [SExpr::Float(zero)] = parse_canonical("-0.0")?[..] else { panic!() };
+    assert!(zero.is_sign_positive());
+    let [SExpr::Float(nan)] = parse_canonical("-nan.0")?[..] else { panic!() };
+    assert!(nan.is_nan() && nan.is_sign_positive());
+
+    // Other numbers are left alone.
+    assert_eq!(parse_canonical("(-2.5 17)")?, parse("(-2.5 17)")?);
+    // Since rationals are not supported, `1/3` is a symbol rather than a number.
+    assert_eq!(parse_canonical("1/3")?, [SExpr::Symbol("1/3".to_string())]);
+
+    // Without the option, numbers are parsed as written.
+    let [SExpr::Float(zero)] = parse("-0.0")?[..] else { panic!() };
+    assert!(zero.is_sign_negative());
+    assert_eq!(parse("#xff")?, [SExpr::Int(255, Radix::Hexadecimal)]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn digit_separators() -> Result<()> {
+    let options =
+      ParseOptions { allow_digit_separators: true, ..ParseOptions::default() };
+
+    assert_eq!(
+      parse_with_options("(1_000 -2_5.0_1)", &options)?,
+      [[1000.into(), SExpr::Float(-25.01)].into_iter().collect::<SExpr>()],
+    );
+
+    let error = parse_with_options("(a 1__0)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 7 });
+    assert_eq!(error.kind, syntax::ErrorKind::InvalidToken);
+
+    let error = parse_with_options("9_999_999_999", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 0, end: 13 });
+    assert_eq!(error.kind, syntax::ErrorKind::IntegerOverflow);
+
+    // Without the option, a number with separators is just a symbol.
+    assert_eq!(parse("1_000")?, [SExpr::from("1_000")]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_special_floats() -> Result<()> {
+    let floats: Vec<_> = parse("+inf.0 -inf.0 +nan.0 -nan.0")?
+      .into_iter()
+      .map(|sexpr| match sexpr {
+        SExpr::Float(value) => value,
+        _ => panic!("expected a float, found {sexpr}"),
+      })
+      .collect();
+
+    assert!(floats[0].is_infinite() && floats[0].is_sign_positive());
+    assert!(floats[1].is_infinite() && floats[1].is_sign_negative());
+    assert!(floats[2].is_nan());
+    assert!(floats[3].is_nan());
+
+    // Non-finite floats should round-trip through Display.
+    for value in &floats {
+      let displayed = SExpr::Float(*value).to_string();
+      assert_eq!(parse(&displayed)?[0].to_string(), displayed);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn route_special_numbers() -> Result<()> {
+    let options =
+      ParseOptions { allow_digit_separators: true, ..ParseOptions::default() };
+    let program = parse_with_options(
+      "+inf.0 -nan.0 inf nan +inf -nan.1 inf.0 1_000 -1_0.5",
+      &options,
+    )?;
+
+    assert!(matches!(program[0], SExpr::Float(value) if value == f64::INFINITY));
+    assert!(matches!(program[1], SExpr::Float(value) if value.is_nan()));
+    for sexpr in &program[2..7] {
+      assert!(matches!(sexpr, SExpr::Symbol(_)), "expected a symbol, found {sexpr}");
+    }
+    assert_eq!(program[7], SExpr::from(1000));
+    assert_eq!(program[8], SExpr::Float(-10.5));
+
+    // Special floats written with a radix prefix should never become integers.
+    for input in ["#x+inf.0", "#d-nan.0", "#binf"] {
+      assert!(!matches!(parse(input).as_deref(), Ok([SExpr::Int(..)])), "{input}");
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn display_float() -> Result<()> {
+    // Finite values should round-trip losslessly.
+    for value in [0.1, 1.0, -2.5, f64::MAX, 5e-324, 0.1 + 0.2] {
+      let displayed = SExpr::Float(value).to_string();
+      assert_eq!(parse(&displayed)?, [SExpr::Float(value)], "{displayed}");
+    }
+
+    assert_eq!(SExpr::Float(1.0).to_string(), "1.0");
+    assert_eq!(SExpr::Float(f64::INFINITY).to_string(), "+inf.0");
+    assert_eq!(SExpr::Float(f64::NEG_INFINITY).to_string(), "-inf.0");
+    assert_eq!(SExpr::Float(f64::NAN).to_string(), "+nan.0");
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_bool() -> Result<()> {
+    assert_eq!(parse("#t #true")?, [SExpr::Bool(true), SExpr::Bool(true)]);
+    assert_eq!(parse("#f #false")?, [SExpr::Bool(false), SExpr::Bool(false)]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_char() -> Result<()> {
+    assert_eq!(
+      parse(r"(#\a #\) #\space #\x3bb)")?,
+      [[SExpr::Char('a'), SExpr::Char(')'), SExpr::Char(' '), SExpr::Char('λ')]
+        .into_iter()
+        .collect::<SExpr>()],
+    );
+
+    let error = parse(r"(a #\bogus)").unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 10 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::InvalidCharacter { found: r"#\bogus".to_string() },
+    );
+
+    for input in [r"#\a", r"#\(", r"#\newline", r"#\x1", r"#\x3000"] {
+      assert_eq!(parse(input)?[0].to_string(), input);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_list() -> Result<()> {
+    // Can we parse empty lists?
+    parse("()")?;
+    parse("[]")?;
+    parse("{}")?;
+
+    // Can we parse normal lists?
+    parse("(1 2 3)")?;
+    parse("[1 2 3]")?;
+    parse("{1 2 3}")?;
+
+    // Can we parse nested lists?
+    parse("(1 [2 {3}])")?;
+    parse("{1 [2 3]}")?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn preserve_brackets() -> Result<()> {
+    assert_eq!(parse("(1 2)")?[0].to_string(), "(1 2)");
+    assert_eq!(parse("[1 2]")?[0].to_string(), "[1 2]");
+    assert_eq!(parse("{1 [2 (3)]}")?[0].to_string(), "{1 [2 (3)]}");
+    assert_ne!(parse("(1 2)")?, parse("[1 2]")?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn mismatched_brackets() {
+    let error = parse("(a]").unwrap_err();
+    assert_eq!(error.span, Span { start: 2, end: 3 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnexpectedBracket {
+        expected: TokenKind::RParen,
+        found: TokenKind::RBracket,
+        opener: Span { start: 0, end: 1 },
+      }
+    );
+
+    // The opener is that of the innermost unclosed list.
+    let error = parse("(a [b (c)})").unwrap_err();
+    assert_eq!(error.span, Span { start: 9, end: 10 });
+    assert!(matches!(
+      error.kind,
+      syntax::ErrorKind::UnexpectedBracket { opener: Span { start: 3, end: 4 }, .. }
+    ));
+  }
+
+  #[test]
+  fn structural_equality() -> Result<()> {
+    let lhs = parse("(a [+nan.0] {b -nan.0})")?;
+    let rhs = parse("(a [+nan.0] {b -nan.0})")?;
+    // IEEE 754 equality never considers a NaN to be equal to itself.
+    assert_ne!(lhs, rhs);
+    assert!(lhs.iter().zip(&rhs).all(|(lhs, rhs)| lhs.structural_eq(rhs)));
+
+    let structural_eq = |lhs: &str, rhs: &str| -> Result<bool> {
+      Ok(super::parse_single(lhs)?.structural_eq(&super::parse_single(rhs)?))
+    };
+    assert!(structural_eq("#0=(1 2.5 \"c\")", "#0=(1 2.5 \"c\")")?);
+    assert!(!structural_eq("+nan.0", "-nan.0")?);
+    assert!(!structural_eq("0.0", "-0.0")?);
+    assert!(!structural_eq("(1 2)", "[1 2]")?);
+    assert!(!structural_eq("#xff", "255")?);
+    assert!(!structural_eq("1.0", "1")?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn compare_ignoring_case() -> Result<()> {
+    let [upper, lower] = [super::parse_single("Foo")?, super::parse_single("foo")?];
+    assert!(upper.eq_ignore_case("foo"));
+    assert!(lower.eq_ignore_case("FOO"));
+    assert!(!upper.eq_ignore_case("bar"));
+    assert!(!super::parse_single("\"foo\"")?.eq_ignore_case("foo"));
+
+    // The original case is preserved.
+    assert_ne!(upper, lower);
+    assert_eq!(upper.to_string(), "Foo");
+    assert_eq!(lower.to_string(), "foo");
+
+    Ok(())
+  }
+
+  #[test]
+  fn value_equality() -> Result<()> {
+    let value_eq = |lhs: &str, rhs: &str| -> Result<bool> {
+      Ok(super::parse_single(lhs)?.value_eq(&super::parse_single(rhs)?))
+    };
+
+    assert!(value_eq("(1 2)", "[1 2]")?);
+    assert!(value_eq("(a {b [c]})", "[a {b (c)}]")?);
+    assert!(value_eq("(#xff #b1)", "(255 1)")?);
+    assert!(value_eq("#0=[a]", "#0=(a)")?);
+
+    assert!(!value_eq("(1 2)", "[1 2 3]")?);
+    assert!(!value_eq("(1 2)", "[2 1]")?);
+    assert!(!value_eq("#0=(a)", "#1=(a)")?);
+    assert!(!value_eq("(a)", "a")?);
+    assert!(!value_eq("1", "1.0")?);
+    // Maps are not lists, regardless of their brackets.
+    let options = ParseOptions { braces_as_maps: true, ..ParseOptions::default() };
+    let map = parse_with_options("{a b}", &options)?;
+    assert!(!map[0].value_eq(&super::parse_single("(a b)")?));
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_abbreviations() -> Result<()> {
+    assert_eq!(parse("'x")?, parse("(quote x)")?);
+    assert_eq!(
+      parse("`(a ,b ,@c)")?,
+      parse("(quasiquote (a (unquote b) (unquote-splicing c)))")?
+    );
+    assert_eq!(parse("''x")?, parse("(quote (quote x))")?);
+
+    let error = parse("a '").unwrap_err();
+    assert_eq!(error.span, Span { start: 2, end: 3 });
+    assert_eq!(error.kind, syntax::ErrorKind::UnexpectedEof { expected: "a datum" });
+    assert_eq!(error.to_string(), "unexpected end of input, expected a datum");
+
+    let error = parse("(a ')").unwrap_err();
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnexpectedToken { found: TokenKind::RParen }
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn check_unquote_depth() -> Result<()> {
+    let options = ParseOptions { check_unquote_depth: true, ..ParseOptions::default() };
+
+    parse_with_options("`(a ,b ,@c)", &options)?;
+    parse_with_options("``(a ,,b)", &options)?;
+    parse_with_options("`(a '(,b))", &options)?;
+    // Without the option, unquotes are not checked.
+    parse(",b")?;
+
+    let error = parse_with_options("(a ,b)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 4 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnquoteOutsideQuasiquote { found: TokenKind::Unquote }
+    );
+    assert!(parse_with_options("`(a ,(b ,@c))", &options).is_err());
+    assert!(parse_with_options("`,a ,b", &options).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn detect_abbreviations() -> Result<()> {
+    let x = SExpr::Symbol("x".to_string());
+
+    let tree = parse("'x `x ,x ,@x")?;
+    assert_eq!(tree[0].as_quoted(), Some(&x));
+    assert_eq!(tree[1].as_quasiquoted(), Some(&x));
+    assert_eq!(tree[2].as_unquoted(), Some(&x));
+    assert_eq!(tree[3].as_unquote_spliced(), Some(&x));
+
+    // Other lists should not be detected as abbreviations.
+    let tree = parse("(quote) (quote x y) (foo x) x")?;
+    assert!(tree.iter().all(|sexpr| sexpr.as_quoted().is_none()));
+    assert_eq!(tree[0].as_quasiquoted(), None);
+
+    Ok(())
+  }
+
+  #[test]
+  fn unexpected_dot() {
+    let error = |input| parse(input).unwrap_err();
+    let dot = |start| syntax::Error {
+      span: Span { start, end: start + 1 },
+      kind: syntax::ErrorKind::UnexpectedDot,
+    };
+
+    assert_eq!(error("."), dot(0));
+    assert_eq!(error("(. )"), dot(1));
+    assert_eq!(error("(a . b . c)"), dot(3));
+    assert_eq!(error("'."), dot(1));
+    // Symbols that merely contain dots are unaffected.
+    assert_eq!(parse("... .a a.b"), Ok(vec!["...".into(), ".a".into(), "a.b".into()]));
+  }
+
+  #[test]
+  fn destructure_forms() -> Result<()> {
+    let tree = parse("(if a b c) [if a b c] (if a) (if a b c d) (a) if")?;
+
+    let Some([test, consequent, alternative]) = tree[0].as_form("if") else {
+      panic!("expected (if a b c) to match");
+    };
+    assert_eq!(
+      [test, consequent, alternative].map(SExpr::as_symbol),
+      [Some("a"), Some("b"), Some("c")],
+    );
+    assert!(tree[1].as_form::<3>("if").is_some());
+
+    // Forms with the wrong head or number of elements should be rejected.
+    assert_eq!(tree[2].as_form::<3>("if"), None);
+    assert_eq!(tree[3].as_form::<3>("if"), None);
+    assert_eq!(tree[0].as_form::<3>("when"), None);
+    assert_eq!(tree[4].as_form::<0>("if"), None);
+    assert_eq!(tree[5].as_form::<0>("if"), None);
+
+    assert_eq!(tree[4].as_list(), Some(&[SExpr::from("a")][..]));
+    assert_eq!(tree[5].as_list(), None);
+    assert_eq!(tree[5].as_symbol(), Some("if"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn skip_datum_comments() -> Result<()> {
+    assert_eq!(parse("#;a b")?, parse("b")?);
+    assert_eq!(parse("(a #;(b c) d #;e)")?, parse("(a d)")?);
+    assert_eq!(parse("'#;a b")?, parse("'b")?);
+
+    // A datum comment should skip a whole datum, including any abbreviation prefix.
+    assert_eq!(parse("#;'x y")?, parse("y")?);
+    assert_eq!(parse("#;`(a ,b) y")?, parse("y")?);
+    // Consecutive datum comments should skip consecutive data.
+    assert_eq!(parse("#;#;a b c")?, parse("c")?);
+    assert_eq!(parse("(#;#;'a 'b)")?, parse("()")?);
+
+    let error = parse("#;").unwrap_err();
+    assert_eq!(error.span, Span { start: 0, end: 2 });
+    assert_eq!(error.kind, syntax::ErrorKind::UnexpectedEof { expected: "a datum" });
+
+    let error = parse("(a #;)").unwrap_err();
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnexpectedToken { found: TokenKind::RParen }
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_events() -> Result<()> {
+    use BracketKind::Paren;
+    use Event::*;
+
+    let events = |input| {
+      let mut events = Vec::new();
+      super::parse_events(input, &mut |event| events.push(event)).map(|()| events)
+    };
+    let span = |start, end| Span { start, end };
+    let symbol = |name: &str| SExpr::Symbol(name.to_string());
+
+    assert_eq!(
+      events("(a (b) c)")?,
+      [
+        StartList(Paren, span(0, 1)),
+        Atom(symbol("a"), span(1, 2)),
+        StartList(Paren, span(3, 4)),
+        Atom(symbol("b"), span(4, 5)),
+        EndList(span(5, 6)),
+        Atom(symbol("c"), span(7, 8)),
+        EndList(span(8, 9)),
+      ],
+    );
+    assert_eq!(
+      events("#0='#;x y")?,
+      [
+        Label(0, span(0, 3)),
+        StartList(Paren, span(3, 4)),
+        Atom(symbol("quote"), span(3, 4)),
+        Atom(symbol("y"), span(8, 9)),
+        EndList(span(9, 9)),
+      ],
+    );
+
+    assert_eq!(
+      events("[a)").unwrap_err().kind,
+      syntax::ErrorKind::UnexpectedBracket {
+        expected: TokenKind::RBracket,
+        found: TokenKind::RParen,
+        opener: span(0, 1),
+      }
+    );
+    assert_eq!(events("(a").unwrap_err().span, span(0, 2));
+    assert_eq!(events("(')").unwrap_err(), parse("(')").unwrap_err());
+    assert_eq!(events("a '").unwrap_err(), parse("a '").unwrap_err());
+
+    Ok(())
+  }
+
+  #[cfg(feature = "iterative-parser")]
+  #[test]
+  fn parse_iterative() -> Result<()> {
+    for input in [
+      "(defn fac [n]\n(fac (minus n 1)))\n\n(print (fac 5))",
+      "'(a `(b ,c ,@d)) #;(e) {f #\\g \"h\"}",
+      "#0=(a #1=#2=[b] #0# #2#) #3='c",
+      &format!("{}x{}", "(".repeat(100), ")".repeat(100)),
+      "",
+    ] {
+      assert_eq!(super::parse_iterative(input)?, parse(input)?, "{input}");
+    }
+
+    assert_eq!(super::parse_iterative("(a ]"), parse("(a ]"));
+    assert_eq!(super::parse_iterative("#0=(#1#)"), parse("#0=(#1#)"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_datum_labels() -> Result<()> {
+    use BracketKind::*;
+    use SExpr::*;
+
+    let a = || Symbol("a".to_string());
+
+    // Shared structure.
+    assert_eq!(
+      parse("(#0=(a) #0#)")?,
+      [List(vec![Labelled(0, Box::new(List(vec![a()], Paren))), LabelRef(0)], Paren)],
+    );
+    // Circular structure.
+    assert_eq!(
+      parse("#1=(a #1#)")?,
+      [Labelled(1, Box::new(List(vec![a(), LabelRef(1)], Paren)))]
+    );
+
+    let error = parse("(#0=a #1#)").unwrap_err();
+    assert_eq!(error.span, Span { start: 6, end: 9 });
+    assert_eq!(error.kind, syntax::ErrorKind::UndefinedLabel { label: 1 });
+
+    Ok(())
+  }
+
+  #[test]
+  fn collect_symbols() -> Result<()> {
+    let symbol = |name: &str, start, end, quoted| SymbolOccurrence {
+      name: name.to_string(),
+      span: Span { start, end },
+      quoted,
+    };
+
+    assert_eq!(
+      super::collect_symbols("(define x 1) (+ x 2)")?,
+      [
+        symbol("define", 1, 7, false),
+        symbol("x", 8, 9, false),
+        symbol("+", 14, 15, false),
+        symbol("x", 16, 17, false),
+      ],
+    );
+    assert_eq!(super::collect_symbols("(f 'a #;z (quote b) `(c ,d))")?.len(), 6,);
+    assert_eq!(
+      super::collect_symbols("(f 'a (quote b) `(c ,d))")?,
+      [
+        symbol("f", 1, 2, false),
+        symbol("a", 4, 5, true),
+        symbol("quote", 7, 12, false),
+        symbol("b", 13, 14, true),
+        symbol("c", 18, 19, true),
+        symbol("d", 21, 22, false),
+      ],
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn unsupported_reader_syntax() {
+    let error = parse("#u8(1 2 3)").unwrap_err();
+    assert_eq!(error.span, Span { start: 0, end: 4 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnsupportedReaderSyntax { form: "#u8(".to_string() },
+    );
+
+    let error = parse("(a #(b) c)").unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 5 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnsupportedReaderSyntax { form: "#(".to_string() },
+    );
+  }
+
+  #[test]
+  fn reserved_symbol_start() -> Result<()> {
+    let error = parse("(a #foo)").unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 7 });
+    assert_eq!(error.kind, syntax::ErrorKind::ReservedSymbolStart { ch: '#' });
+    assert_eq!(
+      error.to_string(),
+      "symbol cannot start with `#`, which is reserved for reader syntax"
+    );
+    assert_eq!(
+      parse("#-bar").unwrap_err().kind,
+      syntax::ErrorKind::ReservedSymbolStart { ch: '#' }
+    );
+
+    // Ordinary symbols should be unaffected.
+    assert_eq!(parse("(foo +bar -baz λ)")?[0].as_list().map(<[_]>::len), Some(4));
+
+    // Forms that resemble reader syntax reserved by R7RS should remain unsupported.
+    for input in ["#e1.5", "#x1.5", "#!fold-case", "#|", "#vu8(", "#12"] {
+      let kind = parse(&format!("(a {input})")).unwrap_err().kind;
+      assert!(
+        matches!(kind, syntax::ErrorKind::UnsupportedReaderSyntax { .. }),
+        "{input}"
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn empty_symbol() -> Result<()> {
+    // A lone `#` is neither reader syntax nor a symbol.
+    for input in ["#", "(a # b)", "'#", "(#)"] {
+      let error = parse(input).unwrap_err();
+      assert_eq!(error.kind, syntax::ErrorKind::EmptySymbol, "{input}");
+      assert_eq!(error.span.end - error.span.start, 1, "{input}");
+    }
+    assert_eq!(parse("(a # b)").unwrap_err().span, Span { start: 3, end: 4 });
+
+    // The empty symbol may be written between pipes, however.
+    assert_eq!(parse("||")?, [SExpr::Symbol(String::new())]);
+    let tree = parse("(a || b)")?;
+    assert_eq!(
+      tree[0].as_list().map(|list| &list[1]),
+      Some(&SExpr::Symbol(String::new()))
+    );
+
+    // Anything else after `#` is still unsupported reader syntax.
+    assert!(matches!(
+      parse("#(").unwrap_err().kind,
+      syntax::ErrorKind::UnsupportedReaderSyntax { .. }
+    ));
+
+    // Leniently, a lone `#` is a symbol like any other unsupported reader syntax.
+    let options = ParseOptions { lenient_hash: true, ..ParseOptions::default() };
+    assert_eq!(parse_with_options("#", &options)?, [SExpr::Symbol("#".to_string())]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn normalize_nfc() -> Result<()> {
+    let options = ParseOptions { normalize_nfc: true, ..ParseOptions::default() };
+
+    // The precomposed and decomposed forms of `é` should only be equal after
+    // normalisation.
+    assert_ne!(parse("caf\u{e9}")?, parse("cafe\u{301}")?);
+    assert_eq!(
+      parse_with_options("caf\u{e9}", &options)?,
+      parse_with_options("cafe\u{301}", &options)?,
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn forbid_tabs() -> Result<()> {
+    parse("(a\tb)")?;
+
+    let options = ParseOptions { forbid_tabs: true, ..ParseOptions::default() };
+    let error = parse_with_options("(a\tb)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 2, end: 3 });
+    assert_eq!(error.kind, syntax::ErrorKind::TabInSource);
+
+    Ok(())
+  }
+
+  #[test]
+  fn commas_as_whitespace() -> Result<()> {
+    let options = ParseOptions { commas_as_whitespace: true, ..ParseOptions::default() };
+    assert_eq!(parse_with_options("(1, 2, 3)", &options)?, parse("(1 2 3)")?);
+    assert_eq!(parse("(1, 2, 3)")?, parse("(1 (unquote 2) (unquote 3))")?);
+
+    let error = parse_with_options("(1 ,@2)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 5 });
+    assert_eq!(error.kind, syntax::ErrorKind::InvalidToken);
+
+    Ok(())
+  }
+
+  #[test]
+  fn lenient_invalid() -> Result<()> {
+    let options = ParseOptions { lenient_invalid: true, ..ParseOptions::default() };
+
+    let program = parse_with_options("(a \\ b)", &options)?;
+    let invalid =
+      SExpr::Invalid { text: "\\".to_string(), span: Span { start: 3, end: 4 } };
+    assert_eq!(
+      program,
+      [[SExpr::from("a"), invalid, SExpr::from("b")].into_iter().collect()]
+    );
+    assert_eq!(program[0].to_string(), "(a \\ b)");
+
+    // Invalid tokens are still rejected by default.
+    let error = parse("(a \\ b)").unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 4 });
+    assert_eq!(error.kind, syntax::ErrorKind::InvalidToken);
+
+    Ok(())
+  }
+
+  #[test]
+  fn custom_symbol_chars() -> Result<()> {
+    let options = ParseOptions {
+      symbol_char: Some(|c| c != '.' && c != ':'),
+      ..ParseOptions::default()
+    };
+
+    let symbol = |name: &str| SExpr::Symbol(name.to_string());
+    assert_eq!(parse("a-b?")?, [symbol("a-b?")]);
+    assert_eq!(parse_with_options("a-b?", &options)?, [symbol("a-b?")]);
+    assert_eq!(parse("a.b")?, [symbol("a.b")]);
+    assert_eq!(
+      parse_with_options("a.b", &options).unwrap_err().kind,
+      syntax::ErrorKind::InvalidToken
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_maps() -> Result<()> {
+    use SExpr::*;
+
+    let options = ParseOptions { braces_as_maps: true, ..ParseOptions::default() };
+    let symbol = |name: &str| Symbol(name.to_string());
+
+    let tree = parse_with_options("{a 1 b 2}", &options)?;
+    assert_eq!(
+      tree,
+      [Map(vec![
+        (symbol("a"), Int(1, Radix::Decimal)),
+        (symbol("b"), Int(2, Radix::Decimal))
+      ])]
+    );
+    assert_eq!(tree[0].to_string(), "{a 1 b 2}");
+    assert!(tree[0].duplicate_keys().is_empty());
+
+    let tree = parse_with_options("{a 1 b 2 a 3 a 4}", &options)?;
+    assert_eq!(tree[0].duplicate_keys(), [&symbol("a")]);
+
+    let error = parse_with_options("(x {a})", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 6 });
+    assert_eq!(error.kind, syntax::ErrorKind::OddMapEntries);
+
+    Ok(())
+  }
+
+  #[test]
+  fn strict_brackets() -> Result<()> {
+    let options = ParseOptions { strict_brackets: true, ..ParseOptions::default() };
+
+    parse("{1 2}")?;
+    parse_with_options("(1 [2])", &options)?;
+
+    let error = parse_with_options("(1 {2})", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 3, end: 4 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::ReservedBracket { found: TokenKind::LBrace }
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn max_bytes() -> Result<()> {
+    let options = ParseOptions { max_bytes: Some(10), ..ParseOptions::default() };
+
+    parse_with_options("(a b c d)", &options)?;
+
+    let error = parse_with_options("(a b c d e)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 10, end: 11 });
+    assert_eq!(error.kind, syntax::ErrorKind::InputTooLarge { limit: 10 });
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_spanned() -> Result<()> {
+    let options = ParseOptions::default();
+    let mut forms = Parser::new("#;x (a b)\n  'c ; d", &options).spanned();
+
+    assert_eq!(
+      forms.next(),
+      Some(Ok((parse("(a b)")?.remove(0), Span { start: 4, end: 9 })))
+    );
+    assert_eq!(
+      forms.next(),
+      Some(Ok((parse("'c")?.remove(0), Span { start: 12, end: 14 })))
+    );
+    assert_eq!(forms.next(), None);
+
+    // Iteration should end after the first error.
+    let mut forms = Parser::new("a ) b", &options).spanned();
+    assert!(forms.next().unwrap().is_ok());
+    assert!(forms.next().unwrap().is_err());
+    assert_eq!(forms.next(), None);
+
+    Ok(())
+  }
+
+  #[test]
+  fn peek_datum() -> Result<()> {
+    let options = ParseOptions::default();
+    let mut parser = Parser::new("(a b) 'c", &options);
+
+    let peeked = parser.peek_datum().cloned();
+    assert_eq!(peeked, Some(parse("(a b)")?.remove(0)));
+    // Peeking again should not consume the buffered datum.
+    assert_eq!(parser.peek_datum().cloned(), peeked);
+    assert_eq!(parser.next_datum().transpose()?, peeked);
+
+    assert_eq!(parser.peek_datum().cloned(), Some(parse("'c")?.remove(0)));
+    let mut forms = parser.spanned();
+    assert_eq!(
+      forms.next(),
+      Some(Ok((parse("'c")?.remove(0), Span { start: 6, end: 8 })))
+    );
+    assert_eq!(forms.next(), None);
+
+    // An error should be buffered and returned by the next call to next_datum.
+    let mut parser = Parser::new(")", &options);
+    assert_eq!(parser.peek_datum(), None);
+    assert!(parser.next_datum().unwrap().is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn data_only() -> Result<()> {
+    // Abbreviations are expanded when reading code, but rejected when reading data.
+    assert_eq!(
+      parse("'x")?,
+      [[SExpr::from("quote"), SExpr::from("x")].into_iter().collect()]
+    );
+    for (input, found, start) in [
+      ("'x", TokenKind::Quote, 0),
+      ("(a `b)", TokenKind::Quasiquote, 3),
+      ("[1 ,c]", TokenKind::Unquote, 3),
+      ("{:k ,@d}", TokenKind::UnquoteSplicing, 4),
+    ] {
+      let error = parse_data(input).unwrap_err();
+      assert_eq!(error.kind, syntax::ErrorKind::AbbreviationInData { found }, "{input}");
+      assert_eq!(error.span.start, start, "{input}");
+    }
+
+    // Plain data, including the long forms of abbreviations, is unaffected.
+    let input = "(name \"luna\") #0=(1 2.5 #t #\\a) (quote x)";
+    assert_eq!(parse_data(input)?, parse(input)?);
+
+    // Abbreviations should also be rejected when parsing into events.
+    let options = ParseOptions { data_only: true, ..ParseOptions::default() };
+    let error =
+      with_parser("(a 'b)", &options, |parser| parser.parse_events(&mut |_| {}))?
+        .unwrap_err();
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::AbbreviationInData { found: TokenKind::Quote }
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn type_predicates() -> Result<()> {
+    let check = |input| {
+      super::parse_single(input).map(|sexpr| {
+        [sexpr.is_self_evaluating(), sexpr.is_symbol(), sexpr.is_list(), sexpr.is_nil()]
+      })
+    };
+
+    for input in ["42", "#x2a", "1.5", "#t", "\"a\"", "#\\a", ":key", "#0=1"] {
+      assert_eq!(check(input)?, [true, false, false, false], "{input}");
+    }
+    assert_eq!(check("a")?, [false, true, false, false]);
+    assert_eq!(check("|a b|")?, [false, true, false, false]);
+    assert_eq!(check("(a b)")?, [false, false, true, false]);
+    assert_eq!(check("'a")?, [false, false, true, false]);
+    assert_eq!(check("()")?, [false, false, true, true]);
+    assert_eq!(check("[]")?, [false, false, true, true]);
+    assert_eq!(check("{}")?, [false, false, true, true]);
+    assert_eq!(check("#0=a")?, [false, true, false, false]);
+    assert_eq!(check("#0=(a)")?, [false, false, true, false]);
+    assert_eq!(check("#0=#1=()")?, [false, false, true, true]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn split_program() -> Result<()> {
+    let span = |start, end| Span { start, end };
+    assert_eq!(split_forms("(a)\n(b c)")?, [("(a)", span(0, 3)), ("(b c)", span(4, 9))]);
+
+    // Formatting within a form is preserved, whereas comments between forms are not.
+    let input = "; a\n'(f  x ; b\n  y)\n\n#;d e";
+    assert_eq!(
+      split_forms(input)?,
+      [("'(f  x ; b\n  y)", span(4, 19)), ("e", span(25, 26))]
+    );
+
+    assert_eq!(split_forms("")?, []);
+    assert_eq!(
+      split_forms("(a) (b").unwrap_err().kind,
+      syntax::ErrorKind::UnmatchedBracket { expected: TokenKind::RParen }
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn reset_parser() -> Result<()> {
+    let options = ParseOptions::default();
+    let mut parser = Parser::new("#0=(a b) #0#", &options);
+    assert_eq!(parser.next_datum().transpose()?, Some(parse("#0=(a b)")?.remove(0)));
+    assert_eq!(parser.peek_datum().cloned(), Some(SExpr::LabelRef(0)));
+
+    // Neither the lookahead nor the labels of the first input should persist.
+    parser.reset("[c] #0#");
+    assert_eq!(parser.next_datum().transpose()?, Some(parse("[c]")?.remove(0)));
+    let error = parser.next_datum().unwrap().unwrap_err();
+    assert_eq!(error.kind, syntax::ErrorKind::UndefinedLabel { label: 0 });
+
+    // Nor should a list that was left unfinished.
+    let mut parser = Parser::new("(a (b", &options);
+    assert!(parser.next_datum().unwrap().is_err());
+    parser.reset("d 'e");
+    let forms: Vec<_> = parser.spanned().collect::<Result<_>>()?;
+    assert_eq!(
+      forms,
+      [
+        (SExpr::from("d"), Span { start: 0, end: 1 }),
+        (parse("'e")?.remove(0), Span { start: 2, end: 4 }),
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_all() -> Result<()> {
+    let (program, errors) = super::parse_all("(a | b) (c) ) (d \"\\q\" (e)) f");
+    assert_eq!(program, parse("(c) f")?);
+    assert_eq!(
+      errors.iter().map(|error| error.span).collect::<Vec<_>>(),
+      [
+        Span { start: 3, end: 4 },
+        Span { start: 12, end: 13 },
+        Span { start: 18, end: 20 }
+      ],
+    );
+
+    let (program, errors) = super::parse_all("a (b");
+    assert_eq!(program, parse("a")?);
+    assert_eq!(errors.len(), 1);
+
+    Ok(())
+  }
+
+  #[test]
+  fn merge_invalid_tokens() -> Result<()> {
+    // A run of adjacent invalid characters should be reported as a single error.
+    let (program, errors) = super::parse_all("a \\\\\\ b (c \\\\ d) \\ \\");
+    assert_eq!(program, parse("a b")?);
+    assert_eq!(
+      errors,
+      [
+        syntax::Error {
+          span: Span { start: 2, end: 5 },
+          kind: syntax::ErrorKind::InvalidToken
+        },
+        syntax::Error {
+          span: Span { start: 11, end: 13 },
+          kind: syntax::ErrorKind::InvalidToken
+        },
+        syntax::Error {
+          span: Span { start: 17, end: 18 },
+          kind: syntax::ErrorKind::InvalidToken
+        },
+        syntax::Error {
+          span: Span { start: 19, end: 20 },
+          kind: syntax::ErrorKind::InvalidToken
+        },
+      ],
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_upto() -> Result<()> {
+    let (program, error) = super::parse_upto("(a) ] (b)");
+    assert_eq!(program, parse("(a)")?);
+    assert_eq!(
+      error,
+      Some((
+        syntax::Error {
+          span: Span { start: 4, end: 5 },
+          kind: syntax::ErrorKind::UnexpectedToken { found: TokenKind::RBracket },
+        },
+        4
+      )),
+    );
+
+    // The position should be the start of the form containing the error.
+    let (program, error) = super::parse_upto("a (b \"\\q\")");
+    assert_eq!(program, parse("a")?);
+    assert_eq!(error.map(|(_, pos)| pos), Some(2));
+
+    assert_eq!(super::parse_upto("a b"), (parse("a b")?, None));
+
+    Ok(())
+  }
+
+  #[test]
+  fn recover_brackets() -> Result<()> {
+    let options = ParseOptions { recover_brackets: true, ..ParseOptions::default() };
+
+    let (program, errors) = parse_all_with_options("(a ] b)", &options);
+    assert_eq!(program, parse("(a) b")?);
+    assert_eq!(
+      errors,
+      [syntax::Error {
+        span: Span { start: 3, end: 4 },
+        kind: syntax::ErrorKind::UnexpectedBracket {
+          expected: TokenKind::RParen,
+          found: TokenKind::RBracket,
+          opener: Span { start: 0, end: 1 },
+        },
+      }],
+    );
+
+    let (program, errors) = parse_all_with_options("((a ] b) c) d", &options);
+    assert_eq!(program, parse("((a) b) c d")?);
+    assert_eq!(errors.len(), 1);
+
+    // Without the option, the list is lost and its intended closer is stray.
+    let (program, errors) = super::parse_all("(a ] b)");
+    assert_eq!(program, parse("b")?);
+    assert_eq!(errors.len(), 2);
+
+    Ok(())
+  }
+
+  #[test]
+  fn max_errors() -> Result<()> {
+    let options = ParseOptions { max_errors: 3, ..ParseOptions::default() };
+
+    let (program, errors) = parse_all_with_options(") ) a ) | b ) )", &options);
+    assert_eq!(program, parse("a")?);
+    assert_eq!(errors.len(), 4);
+    assert_eq!(errors[3].span, Span { start: 8, end: 9 });
+    assert_eq!(errors[3].kind, syntax::ErrorKind::TooManyErrors { limit: 3 });
+
+    // Errors that are recovered from should count towards the limit too.
+    let options = ParseOptions { recover_brackets: true, ..options };
+    let (program, errors) = parse_all_with_options(&"(a] ".repeat(500), &options);
+    assert_eq!(program.len(), 3);
+    assert_eq!(errors.len(), 4);
+    assert_eq!(errors[3].span, Span { start: 14, end: 15 });
+    assert_eq!(errors[3].kind, syntax::ErrorKind::TooManyErrors { limit: 3 });
+
+    // The default limit should bound the number of errors too.
+    let (_, errors) = super::parse_all(&")".repeat(1000));
+    assert_eq!(errors.len(), 101);
+
+    Ok(())
+  }
+
+  #[test]
+  fn max_list_len() -> Result<()> {
+    let options = ParseOptions { max_list_len: Some(3), ..ParseOptions::default() };
+
+    parse_with_options("(a (b c d) [e f g])", &options)?;
+
+    let error = parse_with_options("(a b c \"d\" e)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 7, end: 10 });
+    assert_eq!(error.kind, syntax::ErrorKind::ListTooLong { limit: 3 });
+
+    // The limit applies to nested lists too, and to the first token of an element.
+    let error = parse_with_options("((a b c (d)))", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 8, end: 9 });
+
+    Ok(())
+  }
+
+  #[test]
+  fn max_token_len() -> Result<()> {
+    let options = ParseOptions { max_token_len: Some(5), ..ParseOptions::default() };
+
+    parse_with_options("(hello \"abc\")", &options)?;
+
+    let error = parse_with_options("(a abcdefgh)", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 8, end: 11 });
+    assert_eq!(error.kind, syntax::ErrorKind::TokenTooLong { limit: 5 });
+
+    // The span should not split a multi-byte character.
+    let error = parse_with_options("abcdλ", &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 4, end: 6 });
+
+    Ok(())
+  }
+
+  #[test]
+  fn lenient_hash() -> Result<()> {
+    let options = ParseOptions { lenient_hash: true, ..ParseOptions::default() };
+
+    assert_eq!(
+      parse_with_options("(#weird #t #\\a)", &options)?,
+      [["#weird".into(), true.into(), SExpr::Char('a')].into_iter().collect::<SExpr>()],
+    );
+    assert!(parse("#weird").is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn ignore_bom() -> Result<()> {
+    assert_eq!(strip_bom("\u{FEFF}(a b)"), "(a b)");
+    assert_eq!(strip_bom("(a b)"), "(a b)");
+
+    assert_eq!(parse("\u{FEFF}(a b)")?, parse("(a b)")?);
+    assert_eq!(parse("\u{FEFF}#!/usr/bin/env luna\n(a b)")?, parse("(a b)")?);
+    // Spans are relative to the input after the byte order mark.
+    assert_eq!(parse("\u{FEFF})").unwrap_err().span, Span { start: 0, end: 1 });
+
+    Ok(())
+  }
+
+  #[test]
+  fn ignore_shebang() {
+    const PROGRAM1: &str = "#!/usr/bin/env luna\n(define x 10)\n";
+    assert_eq!(strip_shebang(PROGRAM1), "(define x 10)\n");
+
+    const PROGRAM2: &str = "#!/usr/bin/env luna";
+    assert!(strip_shebang(PROGRAM2).is_empty());
+
+    const PROGRAM3: &str = "#!/usr/bin/env luna\n";
+    assert!(strip_shebang(PROGRAM3).is_empty());
+  }
+
+  #[test]
+  fn display_source() {
+    let options = ParseOptions::default();
+    assert_eq!(options.display_source("(a)"), ("(a)", 0));
+    assert_eq!(options.display_source("\u{FEFF}(a)"), ("(a)", 0));
+    assert_eq!(options.display_source("\u{FEFF}#!luna\n(a)"), ("#!luna\n(a)", 7));
+
+    let options = ParseOptions { strip_shebang: false, ..options };
+    assert_eq!(options.display_source("#!luna\n(a)"), ("#!luna\n(a)", 0));
+  }
+
+  #[test]
+  fn keep_shebang() -> Result<()> {
+    const PROGRAM: &str = "#!/usr/bin/env luna\n(a)";
+    assert_eq!(parse(PROGRAM)?, [["a".into()].into_iter().collect::<SExpr>()]);
+
+    let options = ParseOptions { strip_shebang: false, ..ParseOptions::default() };
+    let error = parse_with_options(PROGRAM, &options).unwrap_err();
+    assert_eq!(error.span, Span { start: 0, end: 14 });
+    assert_eq!(
+      error.kind,
+      syntax::ErrorKind::UnsupportedReaderSyntax { form: "#!/usr/bin/env".to_string() },
+    );
+
+    // The first line is lexed as usual, so it may be parsed leniently.
+    let options = ParseOptions { lenient_hash: true, ..options };
+    assert_eq!(
+      parse_with_options(PROGRAM, &options)?,
+      [
+        "#!/usr/bin/env".into(),
+        "luna".into(),
+        ["a".into()].into_iter().collect::<SExpr>(),
+      ],
+    );
+
+    Ok(())
   }
 }
@@ -0,0 +1,88 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Human-readable rendering of syntax trees.
+
+use std::fmt::Write;
+
+use crate::syntax::{parser::format_float, SExpr};
+
+/// The number of spaces by which each level of nesting is indented.
+const INDENT: usize = 2;
+
+/// Render a syntax tree as an indented outline of its structure.
+///
+/// Each node is written on its own line as the name of its variant, followed by its
+/// value in the case of an atom, with the children of each list, map, or labelled
+/// datum indented beneath it. Lists are annotated with the brackets that delimit them.
+pub fn pretty_ast(tree: &[SExpr]) -> String {
+  let mut output = String::new();
+  for sexpr in tree {
+    pretty_sexpr(&mut output, sexpr, 0);
+  }
+
+  output
+}
+
+/// Render a single symbolic expression at the given depth into the output buffer.
+fn pretty_sexpr(output: &mut String, sexpr: &SExpr, depth: usize) {
+  let indent = " ".repeat(depth * INDENT);
+  match sexpr {
+    SExpr::Symbol(name) => writeln!(output, "{indent}Symbol {name}").unwrap(),
+    SExpr::Keyword(name) => writeln!(output, "{indent}Keyword :{name}").unwrap(),
+    SExpr::String(_) => writeln!(output, "{indent}String {sexpr}").unwrap(),
+    SExpr::Int(..) => writeln!(output, "{indent}Int {sexpr}").unwrap(),
+    SExpr::Float(value) => {
+      writeln!(output, "{indent}Float {}", format_float(*value)).unwrap()
+    },
+    SExpr::Bool(_) => writeln!(output, "{indent}Bool {sexpr}").unwrap(),
+    SExpr::Char(_) => writeln!(output, "{indent}Char {sexpr}").unwrap(),
+    SExpr::List(list, bracket) => {
+      let (opener, closer) = (bracket.opener(), bracket.closer());
+      writeln!(output, "{indent}List {opener}{closer}").unwrap();
+      for sexpr in list {
+        pretty_sexpr(output, sexpr, depth + 1);
+      }
+    },
+    SExpr::Map(entries) => {
+      writeln!(output, "{indent}Map").unwrap();
+      for (key, value) in entries {
+        pretty_sexpr(output, key, depth + 1);
+        pretty_sexpr(output, value, depth + 1);
+      }
+    },
+    SExpr::Labelled(label, datum) => {
+      writeln!(output, "{indent}Labelled #{label}=").unwrap();
+      pretty_sexpr(output, datum, depth + 1);
+    },
+    SExpr::LabelRef(label) => writeln!(output, "{indent}LabelRef #{label}#").unwrap(),
+    SExpr::Invalid { text, span } => {
+      writeln!(output, "{indent}Invalid {text:?} @{span}").unwrap()
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse, Error};
+
+  #[test]
+  fn pretty_nested() -> Result<(), Error> {
+    let tree = parse("[a \"b\" #0=#t] (1.5)")?;
+    assert_eq!(
+      pretty_ast(&tree),
+      concat!(
+        "List []\n",
+        "  Symbol a\n",
+        "  String \"b\"\n",
+        "  Labelled #0=\n",
+        "    Bool #t\n",
+        "List ()\n",
+        "  Float 1.5\n",
+      ),
+    );
+
+    Ok(())
+  }
+}
@@ -0,0 +1,327 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Validation of syntax trees against descriptions of their shape.
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::syntax::{Annotated, AnnotatedNode, SExpr, Span};
+
+/// A description of the shape of a datum, against which syntax trees may be validated.
+///
+/// This is intended for reading configuration files. Schemas are built with the
+/// constructors below, such that a list of `(key value)` pairs whose keys are symbols
+/// is described by `Schema::list_of(Schema::tuple([Schema::symbol(), Schema::any()]))`.
+/// A labelled datum is validated as if it were unlabelled.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Schema(Shape);
+
+/// The shape of a datum that is described by a [`Schema`].
+#[derive(Clone, PartialEq, Debug)]
+enum Shape {
+  /// Any datum.
+  Any,
+  /// A symbol atom.
+  Symbol,
+  /// A keyword atom.
+  Keyword,
+  /// A string atom.
+  String,
+  /// An integer atom.
+  Int,
+  /// A floating-point atom.
+  Float,
+  /// A Boolean atom.
+  Bool,
+  /// A character atom.
+  Char,
+  /// A list whose every element has the given shape.
+  ListOf(Box<Schema>),
+  /// A list whose elements have the given shapes, in order.
+  Tuple(Vec<Schema>),
+  /// A datum with at least one of the given shapes.
+  OneOf(Vec<Schema>),
+}
+
+impl Schema {
+  /// Describe any datum whatsoever.
+  pub fn any() -> Self {
+    Self(Shape::Any)
+  }
+
+  /// Describe a symbol.
+  pub fn symbol() -> Self {
+    Self(Shape::Symbol)
+  }
+
+  /// Describe a keyword.
+  pub fn keyword() -> Self {
+    Self(Shape::Keyword)
+  }
+
+  /// Describe a string.
+  pub fn string() -> Self {
+    Self(Shape::String)
+  }
+
+  /// Describe an integer.
+  pub fn int() -> Self {
+    Self(Shape::Int)
+  }
+
+  /// Describe a floating-point number.
+  pub fn float() -> Self {
+    Self(Shape::Float)
+  }
+
+  /// Describe a Boolean.
+  pub fn bool() -> Self {
+    Self(Shape::Bool)
+  }
+
+  /// Describe a character.
+  pub fn char() -> Self {
+    Self(Shape::Char)
+  }
+
+  /// Describe a list of any length whose every element is described by a schema.
+  pub fn list_of(element: Schema) -> Self {
+    Self(Shape::ListOf(Box::new(element)))
+  }
+
+  /// Describe a list with exactly one element for each of the given schemas, each of
+  /// which describes the element in the same position.
+  pub fn tuple(elements: impl IntoIterator<Item = Schema>) -> Self {
+    Self(Shape::Tuple(elements.into_iter().collect()))
+  }
+
+  /// Describe a datum that is described by at least one of the given schemas.
+  pub fn one_of(alternatives: impl IntoIterator<Item = Schema>) -> Self {
+    Self(Shape::OneOf(alternatives.into_iter().collect()))
+  }
+
+  /// Validate a datum against this schema, returning every violation in order of
+  /// appearance.
+  ///
+  /// The datum conforms to the schema if no violations are returned.
+  pub fn validate(&self, datum: &Annotated<Span>) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    self.validate_into(datum, &mut errors);
+    errors
+  }
+
+  /// Validate every top-level datum of a program against this schema, returning every
+  /// violation in order of appearance.
+  pub fn validate_program(&self, tree: &[Annotated<Span>]) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    for datum in tree {
+      self.validate_into(datum, &mut errors);
+    }
+
+    errors
+  }
+
+  /// Validate a datum against this schema, appending any violations to a vector.
+  fn validate_into(&self, datum: &Annotated<Span>, errors: &mut Vec<SchemaError>) {
+    let span = datum.meta;
+    let unexpected = || SchemaError {
+      span,
+      kind: SchemaErrorKind::UnexpectedDatum {
+        expected: self.to_string(),
+        found: describe(&datum.node),
+      },
+    };
+
+    match (&self.0, &datum.node) {
+      (_, AnnotatedNode::Labelled(_, datum)) => self.validate_into(datum, errors),
+      (Shape::Any, _)
+      | (Shape::Symbol, AnnotatedNode::Atom(SExpr::Symbol(_)))
+      | (Shape::Keyword, AnnotatedNode::Atom(SExpr::Keyword(_)))
+      | (Shape::String, AnnotatedNode::Atom(SExpr::String(_)))
+      | (Shape::Int, AnnotatedNode::Atom(SExpr::Int(..)))
+      | (Shape::Float, AnnotatedNode::Atom(SExpr::Float(_)))
+      | (Shape::Bool, AnnotatedNode::Atom(SExpr::Bool(_)))
+      | (Shape::Char, AnnotatedNode::Atom(SExpr::Char(_))) => {},
+      (Shape::ListOf(element), AnnotatedNode::List(list, _)) => {
+        for datum in list {
+          element.validate_into(datum, errors);
+        }
+      },
+      (Shape::Tuple(elements), AnnotatedNode::List(list, _)) => {
+        if list.len() != elements.len() {
+          let kind =
+            SchemaErrorKind::WrongLength { expected: elements.len(), found: list.len() };
+          errors.push(SchemaError { span, kind });
+          return;
+        }
+
+        for (element, datum) in elements.iter().zip(list) {
+          element.validate_into(datum, errors);
+        }
+      },
+      (Shape::OneOf(alternatives), _) => {
+        // NOTE: Since it is unclear which alternative was intended, the violations of
+        //       each are not reported individually.
+        if !alternatives.iter().any(|schema| schema.validate(datum).is_empty()) {
+          errors.push(unexpected());
+        }
+      },
+      _ => errors.push(unexpected()),
+    }
+  }
+}
+
+impl fmt::Display for Schema {
+  /// Describe this schema as a noun phrase, such as `a symbol`.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match &self.0 {
+      Shape::Any => write!(f, "any datum"),
+      Shape::Symbol => write!(f, "a symbol"),
+      Shape::Keyword => write!(f, "a keyword"),
+      Shape::String => write!(f, "a string"),
+      Shape::Int => write!(f, "an integer"),
+      Shape::Float => write!(f, "a floating-point number"),
+      Shape::Bool => write!(f, "a Boolean"),
+      Shape::Char => write!(f, "a character"),
+      Shape::ListOf(element) => write!(f, "a list of which each element is {element}"),
+      Shape::Tuple(elements) => write!(f, "a list of {} elements", elements.len()),
+      Shape::OneOf(alternatives) => {
+        for (i, alternative) in alternatives.iter().enumerate() {
+          match i {
+            0 => {},
+            _ if i + 1 == alternatives.len() => write!(f, " or ")?,
+            _ => write!(f, ", ")?,
+          }
+          write!(f, "{alternative}")?;
+        }
+
+        Ok(())
+      },
+    }
+  }
+}
+
+/// Describe the kind of a node as a noun phrase, such as `a symbol`.
+fn describe(node: &AnnotatedNode<Span>) -> &'static str {
+  match node {
+    AnnotatedNode::Atom(SExpr::Symbol(_)) => "a symbol",
+    AnnotatedNode::Atom(SExpr::Keyword(_)) => "a keyword",
+    AnnotatedNode::Atom(SExpr::String(_)) => "a string",
+    AnnotatedNode::Atom(SExpr::Int(..)) => "an integer",
+    AnnotatedNode::Atom(SExpr::Float(_)) => "a floating-point number",
+    AnnotatedNode::Atom(SExpr::Bool(_)) => "a Boolean",
+    AnnotatedNode::Atom(SExpr::Char(_)) => "a character",
+    AnnotatedNode::Atom(SExpr::LabelRef(_)) => "a datum label reference",
+    AnnotatedNode::Atom(SExpr::Invalid { .. }) => "an invalid token",
+    AnnotatedNode::Atom(SExpr::List(..) | SExpr::Map(_)) | AnnotatedNode::List(..) => {
+      "a list"
+    },
+    AnnotatedNode::Map(_) => "a map",
+    AnnotatedNode::Atom(SExpr::Labelled(..)) | AnnotatedNode::Labelled(..) => {
+      "a labelled datum"
+    },
+  }
+}
+
+/// A violation of a [`Schema`] by a datum.
+#[derive(Clone, Eq, PartialEq, Error, Debug)]
+#[error("{kind}")]
+pub struct SchemaError {
+  /// The span of the datum that violates the schema.
+  pub span: Span,
+  /// The kind of this violation.
+  pub kind: SchemaErrorKind,
+}
+
+/// The kind of a violation of a [`Schema`].
+#[derive(Clone, Eq, PartialEq, Error, Debug)]
+pub enum SchemaErrorKind {
+  /// A datum was not of the kind that the schema describes.
+  #[error("expected {}, found {}", .expected, .found)]
+  UnexpectedDatum {
+    /// A description of what the schema describes.
+    expected: String,
+    /// A description of the kind of datum that was encountered.
+    found: &'static str,
+  },
+  /// A list did not have as many elements as the schema describes.
+  #[error("expected a list of {} elements, found {} elements", .expected, .found)]
+  WrongLength {
+    /// The number of elements that the schema describes.
+    expected: usize,
+    /// The number of elements that were encountered.
+    found: usize,
+  },
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse_annotated, Error};
+
+  /// A schema for a configuration file of `(key value)` pairs.
+  fn config() -> Schema {
+    Schema::tuple([
+      Schema::symbol(),
+      Schema::one_of([Schema::string(), Schema::int(), Schema::bool()]),
+    ])
+  }
+
+  #[test]
+  fn conforming_document() -> Result<(), Error> {
+    let tree = parse_annotated("(name \"luna\")\n(version 1)\n#0=(debug #t)")?;
+    assert_eq!(config().validate_program(&tree), []);
+
+    let tree = parse_annotated("([a] [b c] ())")?;
+    assert_eq!(
+      Schema::list_of(Schema::list_of(Schema::symbol())).validate(&tree[0]),
+      []
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn nonconforming_document() -> Result<(), Error> {
+    let span = |start, end| Span { start, end };
+
+    let tree = parse_annotated("(name)\n(\"version\" 1)\n(debug 1.5)")?;
+    let errors = config().validate_program(&tree);
+    assert_eq!(
+      errors,
+      [
+        SchemaError {
+          span: span(0, 6),
+          kind: SchemaErrorKind::WrongLength { expected: 2, found: 1 },
+        },
+        SchemaError {
+          span: span(8, 17),
+          kind: SchemaErrorKind::UnexpectedDatum {
+            expected: "a symbol".to_string(),
+            found: "a string",
+          },
+        },
+        SchemaError {
+          span: span(28, 31),
+          kind: SchemaErrorKind::UnexpectedDatum {
+            expected: "a string, an integer or a Boolean".to_string(),
+            found: "a floating-point number",
+          },
+        },
+      ],
+    );
+    assert_eq!(
+      errors[2].to_string(),
+      "expected a string, an integer or a Boolean, found a floating-point number"
+    );
+
+    let tree = parse_annotated("(a 1 b \"c\")")?;
+    let errors = Schema::list_of(Schema::symbol()).validate(&tree[0]);
+    let spans: Vec<_> = errors.iter().map(|error| error.span).collect();
+    assert_eq!(spans, [span(3, 4), span(7, 10)]);
+
+    Ok(())
+  }
+}
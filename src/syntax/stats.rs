@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Metrics over source code and syntax trees.
+
+use std::{iter::Sum, ops::Add};
+
+use crate::syntax::{
+  parse,
+  parser::{strip_bom, strip_shebang},
+  Error, Lexer, SExpr,
+};
+
+/// Counts of the elements of some source code.
+///
+/// Statistics may be combined with `+`, which sums each count and takes the greater of
+/// the two depths.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub struct Stats {
+  /// The number of tokens, or zero if the statistics were computed from a syntax tree.
+  pub tokens: usize,
+  /// The number of top-level forms.
+  pub forms: usize,
+  /// The number of symbols.
+  pub symbols: usize,
+  /// The number of strings.
+  pub strings: usize,
+  /// The number of integer and floating-point numbers.
+  pub numbers: usize,
+  /// The maximum depth to which lists and maps are nested, where a lone atom has a
+  /// depth of zero.
+  pub depth: usize,
+}
+
+impl Stats {
+  /// Format these statistics as a JSON object.
+  pub fn to_json(&self) -> String {
+    format!(
+      r#"{{"tokens":{},"forms":{},"symbols":{},"strings":{},"numbers":{},"depth":{}}}"#,
+      self.tokens, self.forms, self.symbols, self.strings, self.numbers, self.depth,
+    )
+  }
+}
+
+impl Add for Stats {
+  type Output = Stats;
+
+  fn add(self, other: Stats) -> Stats {
+    Stats {
+      tokens: self.tokens + other.tokens,
+      forms: self.forms + other.forms,
+      symbols: self.symbols + other.symbols,
+      strings: self.strings + other.strings,
+      numbers: self.numbers + other.numbers,
+      depth: self.depth.max(other.depth),
+    }
+  }
+}
+
+impl Sum for Stats {
+  fn sum<I: Iterator<Item = Stats>>(iter: I) -> Stats {
+    iter.fold(Stats::default(), Add::add)
+  }
+}
+
+impl SExpr {
+  /// Compute statistics over this expression as a single top-level form.
+  ///
+  /// Since the statistics are computed from the syntax tree, the heads of expanded
+  /// abbreviations such as the `quote` in `'datum` are counted as symbols.
+  pub fn stats(&self) -> Stats {
+    fn go(sexpr: &SExpr) -> Stats {
+      let atom = Stats::default();
+      match sexpr {
+        SExpr::Symbol(_) => Stats { symbols: 1, ..atom },
+        SExpr::String(_) => Stats { strings: 1, ..atom },
+        SExpr::Int(..) | SExpr::Float(_) => Stats { numbers: 1, ..atom },
+        SExpr::Keyword(_)
+        | SExpr::Bool(_)
+        | SExpr::Char(_)
+        | SExpr::LabelRef(_)
+        | SExpr::Invalid { .. } => atom,
+        SExpr::List(list, _) => {
+          let stats: Stats = list.iter().map(go).sum();
+          Stats { depth: stats.depth + 1, ..stats }
+        },
+        SExpr::Map(entries) => {
+          let stats: Stats =
+            entries.iter().map(|(key, value)| go(key) + go(value)).sum();
+          Stats { depth: stats.depth + 1, ..stats }
+        },
+        SExpr::Labelled(_, datum) => go(datum),
+      }
+    }
+
+    Stats { forms: 1, ..go(self) }
+  }
+}
+
+/// Compute statistics over a program, including the number of tokens in its source
+/// code.
+pub fn stats(input: &str) -> Result<Stats, Error> {
+  let program = parse(input)?;
+  let tokens = Lexer::new(strip_shebang(strip_bom(input))).count();
+
+  Ok(Stats { tokens, ..program.iter().map(SExpr::stats).sum() })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn program_stats() -> Result<(), Error> {
+    let stats = stats("(define (f x) (g x \"s\" 1.5)) 'a")?;
+    assert_eq!(
+      stats,
+      Stats { tokens: 15, forms: 2, symbols: 7, strings: 1, numbers: 1, depth: 2 }
+    );
+    assert_eq!(
+      stats.to_json(),
+      r#"{"tokens":15,"forms":2,"symbols":7,"strings":1,"numbers":1,"depth":2}"#
+    );
+
+    assert_eq!(super::stats("")?, Stats::default());
+
+    Ok(())
+  }
+}
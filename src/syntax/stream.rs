@@ -0,0 +1,339 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Lexical analysis of source code that arrives in chunks.
+
+use std::{collections::VecDeque, str};
+
+use crate::syntax::{BytePos, Lexer, LexerOptions, Span, Token, TokenKind, MAX_POS};
+
+/// A token produced by a [`StreamLexer`].
+///
+/// Unlike a [`Token`], this owns its lexeme, since the input that it was lexed from is
+/// discarded once it has been tokenised.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct StreamToken {
+  /// The lexical category of this token.
+  pub kind: TokenKind,
+  /// The lexeme that matched the pattern for this token.
+  pub lexeme: String,
+  /// The span of the whole stream that covers the lexeme.
+  pub span: Span,
+}
+
+/// A lexical analyser for source code that arrives as a stream of byte chunks, such as
+/// over a network connection.
+///
+/// Chunks may split tokens, and even characters, at arbitrary points. A token is only
+/// yielded once it is known to be complete, which is when either it is followed by
+/// something that cannot extend it or the stream has been [finished](Self::finish).
+/// For example, a string literal whose closing quote has not yet arrived is held back
+/// along with every token after it. Any bytes that are not valid UTF-8 are yielded as
+/// a [`TokenKind::Invalid`] token.
+#[derive(Debug)]
+pub struct StreamLexer {
+  /// The options that control the behaviour of the lexer.
+  options: LexerOptions,
+  /// The bytes that have been received but not yet tokenised.
+  buffer: Vec<u8>,
+  /// The position of the start of the buffer within the whole stream.
+  offset: BytePos,
+  /// The tokens that are complete but have not yet been yielded.
+  pending: VecDeque<StreamToken>,
+  /// Whether the end of the stream has been reached.
+  finished: bool,
+}
+
+impl StreamLexer {
+  /// Create a new streaming lexer.
+  pub fn new() -> Self {
+    Self::with_options(LexerOptions::default())
+  }
+
+  /// Create a new streaming lexer with the given options.
+  ///
+  /// The [`emit_eof`](LexerOptions::emit_eof) option is ignored.
+  pub fn with_options(options: LexerOptions) -> Self {
+    Self {
+      options,
+      buffer: Vec::new(),
+      offset: 0,
+      pending: VecDeque::new(),
+      finished: false,
+    }
+  }
+
+  /// Append the next chunk of the stream.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the stream has already been finished.
+  pub fn feed(&mut self, chunk: &[u8]) {
+    assert!(!self.finished, "cannot feed a stream that has been finished");
+    self.buffer.extend_from_slice(chunk);
+    self.tokenise();
+  }
+
+  /// Mark the end of the stream, so that any tokens that were held back in case they
+  /// continued into the next chunk become available.
+  pub fn finish(&mut self) {
+    self.finished = true;
+    self.tokenise();
+  }
+
+  /// Get the next complete token, or `None` if there is none yet.
+  pub fn next_token(&mut self) -> Option<StreamToken> {
+    self.pending.pop_front()
+  }
+
+  /// Check whether the end of the stream has been reached and every token has been
+  /// yielded.
+  pub fn is_exhausted(&self) -> bool {
+    self.finished && self.pending.is_empty()
+  }
+
+  /// Tokenise as much of the buffer as is known to be complete.
+  fn tokenise(&mut self) {
+    loop {
+      let (text, invalid_len) = match str::from_utf8(&self.buffer) {
+        Ok(text) => (text, 0),
+        Err(error) => {
+          let valid = error.valid_up_to();
+          let text = str::from_utf8(&self.buffer[..valid]).unwrap();
+          // NOTE: A sequence that is merely incomplete may still be completed by the
+          //       next chunk, unless there is none.
+          let invalid_len = match error.error_len() {
+            Some(len) => len,
+            None if self.finished => self.buffer.len() - valid,
+            None => 0,
+          };
+          (text, invalid_len)
+        },
+      };
+      let text_is_final = self.finished || invalid_len > 0;
+
+      // NOTE: The end of the text is not necessarily the end of the stream, so no Eof
+      //       token is emitted.
+      let options = LexerOptions { emit_eof: false, ..self.options };
+      let tokens: Vec<_> = Lexer::with_options(text, &options).collect();
+      let mut consumed = 0;
+      let mut complete = true;
+      for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1);
+        let is_final = text_is_final
+          || (!is_unterminated(token)
+            && (next.map_or(text.len(), |next| next.span.start) > token.span.end
+              || is_self_delimiting(token.kind)
+              || next.is_some_and(|next| is_self_delimiting(next.kind))));
+        if !is_final {
+          complete = false;
+          break;
+        }
+
+        self.pending.push_back(StreamToken {
+          kind: token.kind,
+          lexeme: token.lexeme.to_string(),
+          span: token.span.offset(self.offset),
+        });
+        consumed = token.span.end;
+      }
+
+      if complete && text_is_final {
+        // Only whitespace and comments can remain after the last token.
+        consumed = text.len();
+        if invalid_len > 0 {
+          let bytes = &self.buffer[consumed..consumed + invalid_len];
+          let span = Span { start: consumed, end: consumed + invalid_len };
+          self.pending.push_back(StreamToken {
+            kind: TokenKind::Invalid,
+            lexeme: String::from_utf8_lossy(bytes).into_owned(),
+            span: span.offset(self.offset),
+          });
+          consumed += invalid_len;
+        }
+      }
+
+      self.buffer.drain(..consumed);
+      // NOTE: An unbounded stream may eventually pass the largest position that a span
+      //       can refer to, beyond which every span is clamped to it.
+      self.offset = self.offset.saturating_add(consumed).min(MAX_POS);
+      // NOTE: Any valid text that follows an invalid sequence remains to be tokenised.
+      if invalid_len == 0 || self.buffer.is_empty() {
+        break;
+      }
+    }
+  }
+}
+
+impl Default for StreamLexer {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Check whether a token is the opening quote of a string literal or the opening pipe
+/// of a pipe-delimited symbol that has not yet been terminated.
+fn is_unterminated(token: &Token) -> bool {
+  token.kind == TokenKind::Invalid && token.lexeme.starts_with(['"', '|'])
+}
+
+/// Check whether a kind of token can neither be extended nor extend an adjacent token
+/// into another token.
+fn is_self_delimiting(kind: TokenKind) -> bool {
+  kind.is_delimiter() || matches!(kind, TokenKind::Quote | TokenKind::Quasiquote)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::iter;
+
+  use TokenKind::{Char, Invalid, LParen, RParen, Symbol};
+
+  use super::*;
+
+  /// Feed each chunk to a streaming lexer in turn, returning the tokens that become
+  /// available after each chunk and after finishing the stream.
+  fn stream(chunks: &[&[u8]]) -> Vec<Vec<(TokenKind, String)>> {
+    let mut lexer = StreamLexer::new();
+    let drain = |lexer: &mut StreamLexer| {
+      iter::from_fn(|| lexer.next_token())
+        .map(|token| (token.kind, token.lexeme))
+        .collect()
+    };
+
+    let mut batches: Vec<Vec<_>> = Vec::new();
+    for chunk in chunks {
+      lexer.feed(chunk);
+      batches.push(drain(&mut lexer));
+    }
+    lexer.finish();
+    batches.push(drain(&mut lexer));
+    assert!(lexer.is_exhausted());
+
+    batches
+  }
+
+  fn token(kind: TokenKind, lexeme: &str) -> (TokenKind, String) {
+    (kind, lexeme.to_string())
+  }
+
+  #[test]
+  fn split_string() {
+    assert_eq!(
+      stream(&[b"(\"hello ", b"world\" a)"]),
+      [
+        vec![token(LParen, "(")],
+        vec![
+          token(TokenKind::String, "\"hello world\""),
+          token(Symbol, "a"),
+          token(RParen, ")")
+        ],
+        vec![],
+      ],
+    );
+  }
+
+  #[test]
+  fn split_pipe_symbol() {
+    assert_eq!(
+      stream(&[b"(| a", b" b|)"]),
+      [
+        vec![token(LParen, "(")],
+        vec![token(TokenKind::PipeSymbol, "| a b|"), token(RParen, ")")],
+        vec![],
+      ],
+    );
+    // An unterminated symbol is only invalid once the stream is finished.
+    assert_eq!(
+      stream(&[b"|a ", b"b"]),
+      [
+        vec![],
+        vec![],
+        vec![token(Invalid, "|"), token(Symbol, "a"), token(Symbol, "b")]
+      ],
+    );
+  }
+
+  #[test]
+  fn split_symbol() {
+    assert_eq!(
+      stream(&[b"foo", b"bar baz"]),
+      [vec![], vec![token(Symbol, "foobar")], vec![token(Symbol, "baz")]],
+    );
+  }
+
+  #[test]
+  fn split_character() {
+    // The bytes of λ are split across the chunks.
+    assert_eq!(
+      stream(&[b"#\\", b"\xce", b"\xbb)"]),
+      [vec![], vec![], vec![token(Char, "#\\λ"), token(RParen, ")")], vec![]],
+    );
+  }
+
+  #[test]
+  fn spans_across_chunks() {
+    let mut lexer = StreamLexer::new();
+    lexer.feed(b"(a ");
+    lexer.feed(b"bc)");
+
+    let spans: Vec<_> =
+      iter::from_fn(|| lexer.next_token()).map(|token| token.span).collect();
+    assert_eq!(
+      spans,
+      [
+        Span { start: 0, end: 1 },
+        Span { start: 1, end: 2 },
+        Span { start: 3, end: 5 },
+        Span { start: 5, end: 6 }
+      ],
+    );
+  }
+
+  #[test]
+  fn saturate_spans() {
+    let mut lexer = StreamLexer::new();
+    // Pretend that nearly the largest possible amount of input has been consumed.
+    lexer.offset = MAX_POS - 4;
+    lexer.feed(b"(a bc)");
+
+    let spans: Vec<_> =
+      iter::from_fn(|| lexer.next_token()).map(|token| token.span).collect();
+    assert_eq!(
+      spans,
+      [
+        Span { start: MAX_POS - 4, end: MAX_POS - 3 },
+        Span { start: MAX_POS - 3, end: MAX_POS - 2 },
+        Span { start: MAX_POS - 1, end: MAX_POS },
+        Span { start: MAX_POS, end: MAX_POS }
+      ],
+    );
+    assert_eq!(lexer.offset, MAX_POS);
+  }
+
+  #[test]
+  fn finish_stream() {
+    // An unterminated string is only yielded once the stream has been finished.
+    assert_eq!(
+      stream(&[b"a \"b c"]),
+      [vec![token(Symbol, "a")], vec![token(Invalid, "\"b c")]],
+    );
+  }
+
+  #[test]
+  fn invalid_utf8() {
+    assert_eq!(
+      stream(&[b"a \xff b", b"c"]),
+      [
+        vec![token(Symbol, "a"), token(Invalid, "\u{FFFD}")],
+        vec![],
+        vec![token(Symbol, "bc")]
+      ],
+    );
+    // An incomplete sequence at the end of the stream is invalid.
+    assert_eq!(
+      stream(&[b"a\xce"]),
+      [vec![], vec![token(Symbol, "a"), token(Invalid, "\u{FFFD}")]]
+    );
+  }
+}
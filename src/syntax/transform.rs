@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Transformations over syntax trees.
+
+use crate::syntax::SExpr;
+
+/// Rename every symbol in a syntax tree according to a given function.
+///
+/// This recurses into lists and maps, but leaves all other atoms (including keywords and
+/// strings) alone.
+pub fn rename_symbols(tree: &mut [SExpr], f: impl Fn(&str) -> String) {
+  fn go(tree: &mut [SExpr], f: &impl Fn(&str) -> String) {
+    for sexpr in tree {
+      match sexpr {
+        SExpr::Symbol(name) => *name = f(name),
+        SExpr::List(list, _) => go(list, f),
+        SExpr::Map(entries) => {
+          for (key, value) in entries {
+            go(std::slice::from_mut(key), f);
+            go(std::slice::from_mut(value), f);
+          }
+        },
+        SExpr::Labelled(_, datum) => go(std::slice::from_mut(datum), f),
+        SExpr::Keyword(_)
+        | SExpr::String(_)
+        | SExpr::Int(..)
+        | SExpr::Float(_)
+        | SExpr::Bool(_)
+        | SExpr::Char(_)
+        | SExpr::LabelRef(_)
+        | SExpr::Invalid { .. } => {},
+      }
+    }
+  }
+
+  go(tree, &f);
+}
+
+/// Apply a function to every atom in a syntax tree.
+///
+/// This recurses into lists and maps, preserving their structure, and calls the given
+/// function on each symbol, keyword, string, number, Boolean, and character to produce
+/// its replacement.
+pub fn map_atoms(tree: Vec<SExpr>, f: impl Fn(SExpr) -> SExpr) -> Vec<SExpr> {
+  fn go(sexpr: SExpr, f: &impl Fn(SExpr) -> SExpr) -> SExpr {
+    match sexpr {
+      SExpr::List(list, bracket) => {
+        SExpr::List(list.into_iter().map(|sexpr| go(sexpr, f)).collect(), bracket)
+      },
+      SExpr::Map(entries) => SExpr::Map(
+        entries.into_iter().map(|(key, value)| (go(key, f), go(value, f))).collect(),
+      ),
+      SExpr::Labelled(label, datum) => SExpr::Labelled(label, Box::new(go(*datum, f))),
+      SExpr::LabelRef(_) | SExpr::Invalid { .. } => sexpr,
+      SExpr::Symbol(_)
+      | SExpr::Keyword(_)
+      | SExpr::String(_)
+      | SExpr::Int(..)
+      | SExpr::Float(_)
+      | SExpr::Bool(_)
+      | SExpr::Char(_) => f(sexpr),
+    }
+  }
+
+  tree.into_iter().map(|sexpr| go(sexpr, &f)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::{parse, Error};
+
+  #[test]
+  fn kebab_to_snake() -> Result<(), Error> {
+    let mut tree = parse("(foo-bar (baz-qux) \"a-b\")")?;
+    rename_symbols(&mut tree, |name| name.replace('-', "_"));
+    assert_eq!(tree, parse("(foo_bar (baz_qux) \"a-b\")")?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn increment_ints() -> Result<(), Error> {
+    let tree = map_atoms(parse("(1 (2 3) x)")?, |sexpr| match sexpr {
+      SExpr::Int(value, radix) => SExpr::Int(value + 1, radix),
+      _ => sexpr,
+    });
+    assert_eq!(tree, parse("(2 (3 4) x)")?);
+
+    Ok(())
+  }
+}
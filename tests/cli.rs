@@ -0,0 +1,182 @@
+// SPDX-FileCopyrightText: 2022 Severen Redwood <me@severen.dev>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+  env, fs,
+  io::{self, Write},
+  path::PathBuf,
+  process::{Command, Output, Stdio},
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A temporary source file, which is removed when dropped so that it is cleaned up even
+/// if a test fails.
+struct TempSource(PathBuf);
+
+impl TempSource {
+  /// Create a new temporary source file with the given contents.
+  fn new(contents: &str) -> io::Result<Self> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let count = COUNT.fetch_add(1, Ordering::Relaxed);
+    let name = format!("luna-cli-{}-{count}.scm", std::process::id());
+    let source = Self(env::temp_dir().join(name));
+    fs::write(&source.0, contents)?;
+    Ok(source)
+  }
+}
+
+impl Drop for TempSource {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.0);
+  }
+}
+
+/// Run Luna with the given arguments followed by the path of a temporary source file
+/// with the given contents, returning its output and the path of the file, which no
+/// longer exists.
+fn run_luna(contents: &str, args: &[&str]) -> io::Result<(Output, String)> {
+  let source = TempSource::new(contents)?;
+  let output =
+    Command::new(env!("CARGO_BIN_EXE_luna")).args(args).arg(&source.0).output()?;
+  Ok((output, source.0.display().to_string()))
+}
+
+#[test]
+fn print_ast() -> io::Result<()> {
+  let (output, _) = run_luna("(+ 1 (* 2 3))", &["--ast"])?;
+
+  assert!(output.status.success());
+  assert_eq!(
+    String::from_utf8_lossy(&output.stdout),
+    concat!(
+      "List ()\n",
+      "  Symbol +\n",
+      "  Int 1\n",
+      "  List ()\n",
+      "    Symbol *\n",
+      "    Int 2\n",
+      "    Int 3\n",
+    ),
+  );
+
+  Ok(())
+}
+
+#[test]
+fn print_stats() -> io::Result<()> {
+  const PROGRAM: &str = "(define (f x)\n  (g x \"s\" 1.5))\n'a\n";
+  let (text, _) = run_luna(PROGRAM, &["stats"])?;
+  let (json, _) = run_luna(PROGRAM, &["stats", "--json"])?;
+
+  assert!(text.status.success());
+  assert_eq!(
+    String::from_utf8_lossy(&text.stdout),
+    "tokens: 15\nforms: 2\nsymbols: 7\nstrings: 1\nnumbers: 1\ndepth: 2\n",
+  );
+  assert!(json.status.success());
+  assert_eq!(
+    String::from_utf8_lossy(&json.stdout),
+    "{\"tokens\":15,\"forms\":2,\"symbols\":7,\"strings\":1,\"numbers\":1,\"depth\":2}\n",
+  );
+
+  Ok(())
+}
+
+#[test]
+fn report_errors() -> io::Result<()> {
+  let (output, path) = run_luna("(define x 1)\n(f | x)\n(g x)\n(h \"\\q\")\n", &[])?;
+
+  assert!(!output.status.success());
+  // The forms without errors should still be parsed.
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains(r#"Symbol("define")"#));
+  assert!(stdout.contains(r#"Symbol("g")"#));
+
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains(&format!("{path}:2:4: encountered invalid token\n")));
+  assert!(stderr.contains(&format!("{path}:4:5: invalid escape sequence `\\q`\n")));
+  assert!(stderr.contains("due to 2 previous errors"));
+
+  Ok(())
+}
+
+#[test]
+fn report_errors_after_shebang() -> io::Result<()> {
+  const PROGRAM: &str = "\u{FEFF}#!/usr/bin/env luna\n(a))\n";
+
+  // Errors should be located within the file as written, including the shebang line.
+  let (output, path) = run_luna(PROGRAM, &[])?;
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains(&format!("{path}:2:4: unexpected `)`\n")), "{stderr}");
+
+  let (output, path) = run_luna(PROGRAM, &["--ast"])?;
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains(&format!("--> {path}:2:4\n")), "{stderr}");
+  assert!(stderr.contains("2 | (a))\n  |    ^\n"), "{stderr}");
+
+  Ok(())
+}
+
+#[test]
+fn check_formatting() -> io::Result<()> {
+  let (formatted_check, _) = run_luna("(define x 1)\n", &["--format", "--check"])?;
+  let (messy_check, _) = run_luna("(define   x\n 1)", &["--format", "--check"])?;
+  let (messy_format, _) = run_luna("(define   x\n 1)", &["--format"])?;
+
+  assert!(formatted_check.status.success());
+  assert!(!messy_check.status.success());
+  assert!(messy_format.status.success());
+  assert_eq!(String::from_utf8_lossy(&messy_format.stdout), "(define x 1)\n");
+
+  Ok(())
+}
+
+#[test]
+fn format_script() -> io::Result<()> {
+  let script = "#!/usr/bin/env luna\n; Say hello.\n(display \"hello\") ; greet\n";
+  let (check, _) = run_luna(script, &["--format", "--check"])?;
+  let (format, _) = run_luna(script, &["--format"])?;
+  let (error, path) = run_luna("#!/usr/bin/env luna\n(a))\n", &["--format"])?;
+
+  // The shebang line and comments should survive formatting.
+  assert!(check.status.success());
+  assert_eq!(String::from_utf8_lossy(&format.stdout), script);
+
+  // Errors should be located within the file as written.
+  assert!(!error.status.success());
+  let stderr = String::from_utf8_lossy(&error.stderr);
+  assert!(stderr.contains(&format!("--> {path}:2:4\n")), "{stderr}");
+  assert!(stderr.contains("2 | (a))\n  |    ^\n"), "{stderr}");
+
+  Ok(())
+}
+
+#[test]
+fn read_stdin() -> io::Result<()> {
+  let run = |args: &[&str]| {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_luna"))
+      .args(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()?;
+    child.stdin.take().unwrap().write_all(b"(a b)\n(c |)\n")?;
+    child.wait_with_output()
+  };
+
+  // A program that is piped in should be parsed rather than starting the REPL.
+  let output = run(&[])?;
+  assert!(!output.status.success());
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains(r#"Symbol("a")"#));
+  assert!(!stdout.contains("Welcome"));
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains("<stdin>:2:4: encountered invalid token\n"));
+
+  // An explicit `-` should read from standard input too.
+  let output = run(&["-"])?;
+  assert_eq!(String::from_utf8_lossy(&output.stdout), stdout);
+
+  Ok(())
+}